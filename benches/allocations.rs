@@ -0,0 +1,144 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use specs::hibitset::BitSet;
+use specs::storage::UnprotectedStorage;
+use specs::world::Index;
+use specs_idvs::IdvStorage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts every allocation and reallocation made by the process.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Returns how many allocations `f` made.
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Fills a storage small enough for the free list to stay inline with the
+/// `smallvec` feature, then removes and reinserts its components. Run with
+/// and without the feature to compare.
+fn small_storage(c: &mut Criterion) {
+    const LEN: Index = 8;
+
+    fn fill_and_churn() -> IdvStorage<u32> {
+        let mut storage = IdvStorage::with_capacity(LEN as usize);
+
+        for i in 0..LEN {
+            storage.replace(i, i);
+        }
+
+        for i in 0..LEN {
+            black_box(storage.take(i));
+        }
+
+        for i in 0..LEN {
+            storage.replace(i, i);
+        }
+
+        storage
+    }
+
+    let made = allocations(|| drop(fill_and_churn()));
+    println!("small_storage: {} allocations for {} components", made, LEN);
+    // The redirects, the data slots and their occupancy bits, all reserved
+    // up front. The free list lives inline. Generations are not reserved and
+    // grow on their own.
+    #[cfg(all(feature = "smallvec", not(feature = "generations")))]
+    assert_eq!(made, 3, "the free list allocated");
+
+    c.bench_function("small_storage", |b| b.iter(fill_and_churn));
+}
+
+/// Refills and cleans a storage the way a world does every frame. Once the
+/// first frame has grown the buffers no clean allocates.
+fn repeated_clean(c: &mut Criterion) {
+    const LEN: Index = 10_000;
+
+    fn frame(storage: &mut IdvStorage<u32>, mask: &BitSet) {
+        for i in (0..LEN).step_by(3) {
+            storage.replace(i, i);
+        }
+
+        unsafe { storage.clean(mask) };
+    }
+
+    let mut mask = BitSet::new();
+
+    for i in (0..LEN).step_by(3) {
+        mask.add(i);
+    }
+
+    let mut storage = IdvStorage::default();
+    frame(&mut storage, &mask);
+    let made = allocations(|| {
+        for _ in 0..100 {
+            frame(&mut storage, &mask);
+        }
+    });
+    println!("repeated_clean: {} allocations in 100 frames", made);
+    assert_eq!(made, 0, "clean allocated");
+
+    c.bench_function("repeated_clean", |b| b.iter(|| frame(&mut storage, &mask)));
+}
+
+/// Restores a storage from snapshots of different lengths, the way a
+/// rollback system does, with `clone_from` and by assigning a fresh clone.
+fn restore(c: &mut Criterion) {
+    const LEN: Index = 10_000;
+
+    let snapshots: Vec<IdvStorage<u64>> = [LEN, LEN / 2, LEN / 10]
+        .iter()
+        .map(|&len| (0..len).map(|i| (i * 3, u64::from(i))).collect())
+        .collect();
+    let restore_all = |storage: &mut IdvStorage<u64>| {
+        for snapshot in &snapshots {
+            storage.clone_from(snapshot);
+        }
+    };
+    let assign_all = |storage: &mut IdvStorage<u64>| {
+        for snapshot in &snapshots {
+            *storage = snapshot.clone();
+        }
+    };
+
+    let mut storage = IdvStorage::default();
+    restore_all(&mut storage);
+    let reused = allocations(|| restore_all(&mut storage));
+    let assigned = allocations(|| assign_all(&mut storage));
+    println!(
+        "restore: {} allocations with clone_from, {} assigning clones",
+        reused, assigned
+    );
+    assert_eq!(reused, 0, "clone_from allocated");
+
+    let mut group = c.benchmark_group("restore");
+    group.bench_function("clone_from", |b| b.iter(|| restore_all(&mut storage)));
+    group.bench_function("clone", |b| b.iter(|| assign_all(&mut storage)));
+    group.finish();
+}
+
+criterion_group!(benches, small_storage, repeated_clean, restore);
+criterion_main!(benches);
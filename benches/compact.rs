@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use specs::world::Index;
+use specs_idvs::IdvStorage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts every allocation and reallocation made by the process.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const LEN: Index = 50_000;
+
+/// A storage whose components are scattered over twice as many slots.
+fn churned() -> IdvStorage<[u64; 4], 4, u32> {
+    let mut storage = IdvStorage::default();
+
+    for i in 0..LEN * 2 {
+        storage.replace(i, [u64::from(i); 4]);
+    }
+
+    for i in (0..LEN * 2).step_by(2) {
+        storage.take(i);
+    }
+
+    storage
+}
+
+fn compact(c: &mut Criterion) {
+    let mut storage = churned();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    storage.compact();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "compact: {} allocations for {} components",
+        allocations, LEN
+    );
+    assert_eq!(allocations, 0, "compact allocated");
+
+    c.bench_function("compact", |b| {
+        b.iter_batched_ref(churned, |storage| storage.compact(), BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(benches, compact);
+criterion_main!(benches);
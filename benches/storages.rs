@@ -0,0 +1,487 @@
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion,
+};
+use specs::hibitset::{BitSet, BitSetLike};
+use specs::storage::{DenseVecStorage, UnprotectedStorage, VecStorage};
+use specs::world::Index;
+use specs_idvs::{BoxedIdvStorage, IdvStorage, PagedIdvStorage};
+
+const RANGE: Index = 10_000;
+const DENSITIES: [u32; 3] = [1, 10, 100];
+
+type Small = u32;
+type Large = [u64; 8];
+type Huge = [u8; 4096];
+/// No niche, so an `Option` around it needs a separate discriminant.
+type NoNiche = [u32; 3];
+
+/// Deterministically picks roughly `density` percent of `0..RANGE`.
+fn indices(density: u32) -> Vec<Index> {
+    (0..RANGE)
+        .filter(|&i| ((u64::from(i) * 2_654_435_761) >> 7) % 100 < u64::from(density))
+        .collect()
+}
+
+/// Returns `indices` in a fixed pseudo-random order.
+fn shuffled(indices: &[Index]) -> Vec<Index> {
+    let mut out = indices.to_vec();
+    let mut x = 0x9e37_79b9_7f4a_7c15_u64;
+
+    for i in (1..out.len()).rev() {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        out.swap(i, (x % (i as u64 + 1)) as usize);
+    }
+
+    out
+}
+
+fn mask(indices: &[Index]) -> BitSet {
+    let mut mask = BitSet::new();
+
+    for &i in indices {
+        mask.add(i);
+    }
+
+    mask
+}
+
+fn filled<S, T>(indices: &[Index]) -> S
+where
+    S: UnprotectedStorage<T> + Default,
+    T: Default,
+{
+    let mut storage = S::default();
+
+    for &i in indices {
+        unsafe { storage.insert(i, T::default()) };
+    }
+
+    storage
+}
+
+fn insert<S, T>(c: &mut Criterion, name: &str, ty: &str)
+where
+    S: UnprotectedStorage<T> + Default,
+    T: Default,
+{
+    let mut group = c.benchmark_group(format!("insert/{}", ty));
+
+    for &density in &DENSITIES {
+        let indices = indices(density);
+        let mask = mask(&indices);
+        group.bench_with_input(BenchmarkId::new(name, density), &indices, |b, indices| {
+            b.iter(|| {
+                let mut storage = filled::<S, T>(indices);
+                unsafe { storage.clean(&mask) };
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn random_get<S, T>(c: &mut Criterion, name: &str, ty: &str)
+where
+    S: UnprotectedStorage<T> + Default,
+    T: Default,
+{
+    let mut group = c.benchmark_group(format!("random_get/{}", ty));
+
+    for &density in &DENSITIES {
+        let indices = indices(density);
+        let order = shuffled(&indices);
+        let mut storage = filled::<S, T>(&indices);
+        group.bench_with_input(BenchmarkId::new(name, density), &order, |b, order| {
+            b.iter(|| {
+                for &i in order {
+                    black_box(unsafe { storage.get(i) });
+                }
+            })
+        });
+        unsafe { storage.clean(mask(&indices)) };
+    }
+
+    group.finish();
+}
+
+fn masked_iter<S, T>(c: &mut Criterion, name: &str, ty: &str)
+where
+    S: UnprotectedStorage<T> + Default,
+    T: Default,
+{
+    let mut group = c.benchmark_group(format!("masked_iter/{}", ty));
+
+    for &density in &DENSITIES {
+        let indices = indices(density);
+        let mask = mask(&indices);
+        let mut storage = filled::<S, T>(&indices);
+        group.bench_with_input(BenchmarkId::new(name, density), &mask, |b, mask| {
+            b.iter(|| {
+                for i in mask.iter() {
+                    black_box(unsafe { storage.get_mut(i) });
+                }
+            })
+        });
+        unsafe { storage.clean(&mask) };
+    }
+
+    group.finish();
+}
+
+fn clean<S, T>(c: &mut Criterion, name: &str, ty: &str)
+where
+    S: UnprotectedStorage<T> + Default,
+    T: Default,
+{
+    let mut group = c.benchmark_group(format!("clean/{}", ty));
+
+    for &density in &DENSITIES {
+        let indices = indices(density);
+        let mask = mask(&indices);
+        group.bench_with_input(BenchmarkId::new(name, density), &indices, |b, indices| {
+            b.iter_batched_ref(
+                || filled::<S, T>(indices),
+                |storage| unsafe { storage.clean(&mask) },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Walks a large, fully occupied range in order. Run with and without the
+/// `prefetch` feature to compare.
+fn sequential_scan(c: &mut Criterion) {
+    const LEN: Index = 200_000;
+
+    let mut group = c.benchmark_group("sequential_scan/large");
+    let indices: Vec<Index> = (0..LEN).collect();
+    let mask = mask(&indices);
+    let mut storage = filled::<IdvStorage<Large, 4, u32>, Large>(&indices);
+
+    group.bench_function("iter", |b| {
+        b.iter(|| {
+            for (_, v) in storage.iter(&mask) {
+                black_box(v[0]);
+            }
+        })
+    });
+    group.bench_function("iter_mut", |b| {
+        b.iter(|| {
+            for (_, v) in storage.iter_mut(&mask) {
+                v[0] = v[0].wrapping_add(1);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Full ordered scan of a storage filled in random order, before and after
+/// `sort_by_index` lines the data up with the indices.
+fn sorted_scan(c: &mut Criterion) {
+    const LEN: Index = 60_000;
+
+    let mut group = c.benchmark_group("sorted_scan/large");
+    let indices: Vec<Index> = (0..LEN).collect();
+    let mask = mask(&indices);
+    let mut storage = filled::<IdvStorage<Large>, Large>(&shuffled(&indices));
+
+    group.bench_function("shuffled", |b| {
+        b.iter(|| {
+            for (_, v) in storage.iter(&mask) {
+                black_box(v[0]);
+            }
+        })
+    });
+
+    storage.sort_by_index();
+
+    group.bench_function("sorted", |b| {
+        b.iter(|| {
+            for (_, v) in storage.iter(&mask) {
+                black_box(v[0]);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Random lookups of known-occupied indices, through the checked accessor
+/// and through `get_unchecked_ref`.
+fn unchecked_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("unchecked_get/small");
+    let indices = indices(10);
+    let order = shuffled(&indices);
+    let storage = filled::<IdvStorage<Small>, Small>(&indices);
+
+    group.bench_function("get_checked", |b| {
+        b.iter(|| {
+            for &i in &order {
+                black_box(storage.get_checked(i).unwrap());
+            }
+        })
+    });
+    group.bench_function("UnprotectedStorage::get", |b| {
+        b.iter(|| {
+            for &i in &order {
+                black_box(unsafe { UnprotectedStorage::get(&storage, i) });
+            }
+        })
+    });
+    group.bench_function("get_unchecked_ref", |b| {
+        b.iter(|| {
+            for &i in &order {
+                black_box(unsafe { storage.get_unchecked_ref(i) });
+            }
+        })
+    });
+
+    group.finish();
+}
+
+/// Repeatedly bumps neighbouring components from all rayon threads. Without
+/// the `cache-aligned` feature several components share a cache line, which
+/// bounces between the cores writing them; run with and without it to
+/// compare.
+#[cfg(feature = "parallel")]
+fn parallel_mutation(c: &mut Criterion) {
+    use specs::rayon::iter::ParallelIterator;
+
+    const LEN: Index = 256;
+    const BUMPS: u64 = 1_000;
+
+    let mut group = c.benchmark_group("parallel_mutation/small");
+    let indices: Vec<Index> = (0..LEN).collect();
+    let mask = mask(&indices);
+    let mut storage = filled::<IdvStorage<u64>, u64>(&indices);
+    println!(
+        "parallel_mutation: {} bytes per slot",
+        storage.memory_usage().data_bytes / storage.capacity(),
+    );
+
+    group.bench_function("par_iter_mut", |b| {
+        b.iter(|| {
+            storage.par_iter_mut(&mask).for_each(|(_, v)| {
+                for _ in 0..BUMPS {
+                    // Volatile, so every bump is written back to memory.
+                    unsafe { std::ptr::write_volatile(v, std::ptr::read_volatile(v) + 1) };
+                }
+            })
+        })
+    });
+
+    group.finish();
+}
+
+/// Resolves random indices through the redirects alone and through to the
+/// data, for a small and a 64 byte component. Redirects live apart from the
+/// data, so resolving costs the same whatever the component size.
+fn resolve(c: &mut Criterion) {
+    fn bench<T: Default>(group: &mut BenchmarkGroup<'_, WallTime>, ty: &str, order: &[Index]) {
+        let indices = indices(10);
+        let storage = filled::<IdvStorage<T>, T>(&indices);
+
+        group.bench_function(BenchmarkId::new("contains", ty), |b| {
+            b.iter(|| {
+                for &i in order {
+                    black_box(storage.contains(i));
+                }
+            })
+        });
+        group.bench_function(BenchmarkId::new("get_checked", ty), |b| {
+            b.iter(|| {
+                for &i in order {
+                    black_box(storage.get_checked(i).is_some());
+                }
+            })
+        });
+    }
+
+    assert_eq!(std::mem::size_of::<Large>(), 64);
+
+    let mut group = c.benchmark_group("resolve");
+    let order = shuffled(&(0..RANGE).collect::<Vec<_>>());
+    bench::<Small>(&mut group, "small", &order);
+    bench::<Large>(&mut group, "large", &order);
+    group.finish();
+}
+
+/// Cleans a large range holding few components, walking the set bits of the
+/// mask as `clean` does and, for comparison, testing every index covered by
+/// the redirects one bit at a time.
+fn sparse_clean(c: &mut Criterion) {
+    const LEN: Index = 200_000;
+
+    let mut group = c.benchmark_group("sparse_clean/small");
+    let indices: Vec<Index> = (0..LEN).step_by(100).collect();
+    let mask = mask(&indices);
+    let fill = || filled::<IdvStorage<Small>, Small>(&indices);
+
+    group.bench_function("set_bits", |b| {
+        b.iter_batched_ref(
+            fill,
+            |storage| unsafe { storage.clean(&mask) },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("every_index", |b| {
+        b.iter_batched_ref(
+            fill,
+            |storage| {
+                for i in 0..LEN {
+                    if mask.contains(i) {
+                        black_box(storage.take(i));
+                    }
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+/// Fills a sparse range with 4 KiB components and cleans it again, inline
+/// and boxed. Prints the bytes each storage spends per data slot, which is
+/// what vacant slots cost.
+fn huge_component(c: &mut Criterion) {
+    fn fill<S: UnprotectedStorage<Huge> + Default>(indices: &[Index]) -> S {
+        let mut storage = S::default();
+
+        for &i in indices {
+            unsafe { storage.insert(i, [0; 4096]) };
+        }
+
+        storage
+    }
+
+    let mut group = c.benchmark_group("huge_component");
+    let indices = indices(10);
+    let mask = mask(&indices);
+
+    let inline = fill::<IdvStorage<Huge>>(&indices);
+    let boxed = fill::<BoxedIdvStorage<Huge>>(&indices);
+    println!(
+        "huge_component: {} bytes per slot inline, {} bytes per slot boxed",
+        inline.memory_usage().data_bytes / inline.capacity(),
+        boxed.memory_usage().data_bytes / boxed.capacity(),
+    );
+
+    group.bench_function("IdvStorage", |b| {
+        b.iter(|| {
+            let mut storage = fill::<IdvStorage<Huge>>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+    group.bench_function("BoxedIdvStorage", |b| {
+        b.iter(|| {
+            let mut storage = fill::<BoxedIdvStorage<Huge>>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+
+    group.finish();
+}
+
+/// Fills and cleans a storage of components without a niche. Prints the
+/// bytes spent per data slot next to the size an `Option` slot would take.
+fn no_niche_component(c: &mut Criterion) {
+    let mut group = c.benchmark_group("no_niche_component");
+    let indices = indices(100);
+    let mask = mask(&indices);
+
+    let storage = filled::<IdvStorage<NoNiche>, NoNiche>(&indices);
+    println!(
+        "no_niche_component: {} bytes per slot, {} bytes per Option slot",
+        storage.memory_usage().data_bytes / storage.capacity(),
+        std::mem::size_of::<Option<NoNiche>>(),
+    );
+
+    group.bench_function("IdvStorage", |b| {
+        b.iter(|| {
+            let mut storage = filled::<IdvStorage<NoNiche>, NoNiche>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+    group.bench_function("DenseVecStorage", |b| {
+        b.iter(|| {
+            let mut storage = filled::<DenseVecStorage<NoNiche>, NoNiche>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+
+    group.finish();
+}
+
+/// Fills a few clusters of indices far above zero and cleans them again,
+/// flat and paged. Prints the memory each storage allocated, which for the
+/// flat storage is dominated by redirects for the unused indices below.
+fn sparse_high_index(c: &mut Criterion) {
+    const BASE: Index = 4_000_000;
+
+    let mut group = c.benchmark_group("sparse_high_index");
+    let indices: Vec<Index> = (0..4)
+        .flat_map(|cluster| (0..250).map(move |i| BASE + cluster * 500_000 + i * 3))
+        .collect();
+    let mask = mask(&indices);
+
+    let flat = filled::<IdvStorage<Small>, Small>(&indices);
+    let paged = filled::<PagedIdvStorage<Small>, Small>(&indices);
+    println!(
+        "sparse_high_index: {} bytes flat, {} bytes paged",
+        flat.memory_usage().total_bytes(),
+        paged.memory_usage().total_bytes(),
+    );
+
+    group.bench_function("IdvStorage", |b| {
+        b.iter(|| {
+            let mut storage = filled::<IdvStorage<Small>, Small>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+    group.bench_function("PagedIdvStorage", |b| {
+        b.iter(|| {
+            let mut storage = filled::<PagedIdvStorage<Small>, Small>(&indices);
+            unsafe { storage.clean(&mask) };
+        })
+    });
+
+    group.finish();
+}
+
+macro_rules! compare {
+    ($bench:ident, $c:expr) => {
+        $bench::<IdvStorage<Small>, Small>($c, "IdvStorage", "small");
+        $bench::<DenseVecStorage<Small>, Small>($c, "DenseVecStorage", "small");
+        $bench::<VecStorage<Small>, Small>($c, "VecStorage", "small");
+        $bench::<IdvStorage<Large>, Large>($c, "IdvStorage", "large");
+        $bench::<DenseVecStorage<Large>, Large>($c, "DenseVecStorage", "large");
+        $bench::<VecStorage<Large>, Large>($c, "VecStorage", "large");
+    };
+}
+
+fn benches(c: &mut Criterion) {
+    compare!(insert, c);
+    compare!(random_get, c);
+    compare!(masked_iter, c);
+    compare!(clean, c);
+    sequential_scan(c);
+    sorted_scan(c);
+    unchecked_get(c);
+    resolve(c);
+    sparse_clean(c);
+    #[cfg(feature = "parallel")]
+    parallel_mutation(c);
+    huge_component(c);
+    no_niche_component(c);
+    sparse_high_index(c);
+}
+
+criterion_group!(storages, benches);
+criterion_main!(storages);
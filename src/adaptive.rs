@@ -0,0 +1,283 @@
+use crate::{missing_component, missing_removal, CapacityError, IdvStorage, RedirectIndex};
+use alloc::vec::Vec;
+use core::mem;
+use specs::hibitset::BitSetLike;
+use specs::storage::{DistinctStorage, UnprotectedStorage};
+use specs::world::Index;
+
+/// Below this many indices the storage always stays sparse, since a flat
+/// vector brings no benefit and would flip back and forth on every insert.
+const MIN_DENSE_RANGE: usize = 64;
+
+// There is a single `Repr` per storage, boxing the sparse one would only add
+// a pointer hop to every access.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Repr<T, const SPARSE_RATIO: usize, I> {
+    Sparse(IdvStorage<T, SPARSE_RATIO, I>),
+    Dense { data: Vec<Option<T>>, len: usize },
+}
+
+/// A storage that switches between an [`IdvStorage`] and a flat
+/// `Vec<Option<T>>` as occupancy crosses a threshold and half of it.
+#[derive(Clone)]
+pub struct AdaptiveStorage<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    repr: Repr<T, SPARSE_RATIO, I>,
+    threshold: f32,
+}
+
+impl<T, const SPARSE_RATIO: usize, I> Default for AdaptiveStorage<T, SPARSE_RATIO, I> {
+    fn default() -> Self {
+        AdaptiveStorage {
+            repr: Repr::Sparse(IdvStorage::default()),
+            threshold: 0.75,
+        }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> AdaptiveStorage<T, SPARSE_RATIO, I> {
+    /// Creates an empty storage that becomes dense at `threshold` occupancy.
+    pub fn with_threshold(threshold: f32) -> Self {
+        AdaptiveStorage {
+            repr: Repr::Sparse(IdvStorage::default()),
+            threshold,
+        }
+    }
+
+    /// Returns the occupancy at which the storage becomes dense.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Changes the occupancy at which the storage becomes dense, converting
+    /// it right away if needed.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+        self.rebalance();
+    }
+
+    /// Returns `true` if the storage currently uses the flat representation.
+    pub fn is_dense(&self) -> bool {
+        matches!(self.repr, Repr::Dense { .. })
+    }
+
+    /// Returns the number of components currently stored.
+    pub fn len(&self) -> usize {
+        match self.repr {
+            Repr::Sparse(ref storage) => storage.len(),
+            Repr::Dense { len, .. } => len,
+        }
+    }
+
+    /// Returns `true` if the storage holds no components.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    pub fn contains(&self, idx: Index) -> bool {
+        self.get_checked(idx).is_some()
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        match self.repr {
+            Repr::Sparse(ref storage) => storage.get_checked(idx),
+            Repr::Dense { ref data, .. } => data.get(idx as usize)?.as_ref(),
+        }
+    }
+
+    /// Returns a mutable reference to the component at `idx`, if any.
+    pub fn get_checked_mut(&mut self, idx: Index) -> Option<&mut T> {
+        match self.repr {
+            Repr::Sparse(ref mut storage) => storage.get_checked_mut(idx),
+            Repr::Dense { ref mut data, .. } => data.get_mut(idx as usize)?.as_mut(),
+        }
+    }
+
+    /// Stores `v` at `idx` and returns the previous component. Panics where
+    /// [`try_insert`](Self::try_insert) fails.
+    pub fn replace(&mut self, idx: Index, v: T) -> Option<T> {
+        match self.try_replace(idx, v) {
+            Ok(old) => old,
+            Err(_) => panic!("no room for a component at index {}", idx),
+        }
+    }
+
+    /// Inserts a value, returning it back if neither the sparse nor the flat
+    /// layout can hold it.
+    pub fn try_insert(&mut self, idx: Index, v: T) -> Result<(), CapacityError<T>> {
+        self.try_replace(idx, v).map(drop)
+    }
+
+    fn try_replace(&mut self, idx: Index, v: T) -> Result<Option<T>, CapacityError<T>> {
+        let i = idx as usize;
+
+        // Growing a flat vector far past its end would leave it mostly empty,
+        // go sparse before allocating that.
+        if let Repr::Dense { ref data, len } = self.repr {
+            if i >= data.len() && !self.dense_enough(len + 1, i + 1) {
+                self.make_sparse();
+
+                if self.is_dense() {
+                    return Err(CapacityError(v));
+                }
+            }
+        }
+
+        let old = match self.repr {
+            Repr::Sparse(ref mut storage) => match storage.get_checked_mut(idx) {
+                Some(slot) => Some(mem::replace(slot, v)),
+                None => {
+                    storage.try_insert(idx, v)?;
+                    None
+                }
+            },
+            Repr::Dense {
+                ref mut data,
+                ref mut len,
+            } => {
+                if i >= data.len() {
+                    data.resize_with(i + 1, || None);
+                }
+
+                let old = data[i].replace(v);
+
+                if old.is_none() {
+                    *len += 1;
+                }
+
+                old
+            }
+        };
+
+        self.rebalance();
+        Ok(old)
+    }
+
+    /// Removes and returns the component at `idx`, if any.
+    pub fn take(&mut self, idx: Index) -> Option<T> {
+        let old = match self.repr {
+            Repr::Sparse(ref mut storage) => storage.take(idx),
+            Repr::Dense {
+                ref mut data,
+                ref mut len,
+            } => {
+                let old = data.get_mut(idx as usize)?.take();
+
+                if old.is_some() {
+                    *len -= 1;
+                }
+
+                old
+            }
+        };
+
+        self.rebalance();
+        old
+    }
+
+    fn range(&self) -> usize {
+        match self.repr {
+            Repr::Sparse(ref storage) => storage.index_end(),
+            Repr::Dense { ref data, .. } => data.len(),
+        }
+    }
+
+    fn dense_enough(&self, len: usize, range: usize) -> bool {
+        len as f32 >= self.threshold / 2.0 * range as f32
+    }
+
+    fn rebalance(&mut self) {
+        let (len, range) = (self.len(), self.range());
+
+        if self.is_dense() {
+            if !self.dense_enough(len, range) {
+                self.make_sparse();
+            }
+        } else if range >= MIN_DENSE_RANGE && len as f32 >= self.threshold * range as f32 {
+            self.make_dense();
+        }
+    }
+
+    fn make_dense(&mut self) {
+        let range = self.range();
+
+        if let Repr::Sparse(ref mut storage) = self.repr {
+            let len = storage.len();
+            let mut data = Vec::new();
+            data.resize_with(range, || None);
+
+            for (idx, v) in storage.drain() {
+                data[idx as usize] = Some(v);
+            }
+
+            self.repr = Repr::Dense { data, len };
+        }
+    }
+
+    /// Does nothing if the components would not fit into the internal slots
+    /// of an [`IdvStorage`].
+    fn make_sparse(&mut self) {
+        if let Repr::Dense { ref mut data, len } = self.repr {
+            if len > I::MAX_SLOTS {
+                return;
+            }
+
+            self.repr = Repr::Sparse(IdvStorage::from_dense(mem::take(data)));
+        }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> UnprotectedStorage<T>
+    for AdaptiveStorage<T, SPARSE_RATIO, I>
+{
+    unsafe fn clean<B>(&mut self, has: B)
+    where
+        B: BitSetLike,
+    {
+        match self.repr {
+            Repr::Sparse(ref mut storage) => {
+                storage.remove_mask(&has);
+            }
+            Repr::Dense {
+                ref mut data,
+                ref mut len,
+            } => {
+                for idx in has.iter() {
+                    if let Some(slot) = data.get_mut(idx as usize) {
+                        if slot.take().is_some() {
+                            *len -= 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.rebalance();
+    }
+
+    unsafe fn get(&self, idx: Index) -> &T {
+        self.get_checked(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    unsafe fn get_mut(&mut self, idx: Index) -> &mut T {
+        self.get_checked_mut(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    unsafe fn insert(&mut self, idx: Index, v: T) {
+        self.replace(idx, v);
+    }
+
+    unsafe fn remove(&mut self, idx: Index) -> T {
+        self.take(idx).unwrap_or_else(|| missing_removal(idx))
+    }
+}
+
+// Both representations keep every index in its own slot.
+unsafe impl<T, const SPARSE_RATIO: usize, I> DistinctStorage
+    for AdaptiveStorage<T, SPARSE_RATIO, I>
+{
+}
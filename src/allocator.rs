@@ -0,0 +1,53 @@
+// Storage buffers are allocated through `A: Allocator`. With the nightly
+// `allocator_api` feature that is the standard library's trait and any
+// allocator can be used. Without it the only allocator is `Global` and the
+// buffers are plain `Vec`s, `alloc_vec!` picks the matching type.
+
+#[cfg(feature = "allocator_api")]
+pub use alloc::alloc::{Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+pub use self::stable::{Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+mod stable {
+    /// Stand-in for the unstable std `Allocator`, which the `allocator_api`
+    /// feature uses instead.
+    pub trait Allocator: crate::sealed::Sealed {}
+
+    /// The global memory allocator, which storages use unless told otherwise.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct Global;
+
+    impl crate::sealed::Sealed for Global {}
+
+    impl Allocator for Global {}
+}
+
+/// Creates an empty buffer in `alloc`, which does not allocate yet.
+#[cfg(feature = "allocator_api")]
+pub(crate) fn new_vec<T, A: Allocator + Clone>(alloc: &A) -> alloc_vec!(T, A) {
+    alloc::vec::Vec::new_in(alloc.clone())
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub(crate) fn new_vec<T, A: Allocator + Clone>(_: &A) -> alloc_vec!(T, A) {
+    alloc::vec::Vec::new()
+}
+
+/// Creates a buffer in `alloc` with room for `capacity` elements.
+#[cfg(feature = "allocator_api")]
+pub(crate) fn vec_with_capacity<T, A: Allocator + Clone>(
+    capacity: usize,
+    alloc: &A,
+) -> alloc_vec!(T, A) {
+    alloc::vec::Vec::with_capacity_in(capacity, alloc.clone())
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub(crate) fn vec_with_capacity<T, A: Allocator + Clone>(
+    capacity: usize,
+    _: &A,
+) -> alloc_vec!(T, A) {
+    alloc::vec::Vec::with_capacity(capacity)
+}
@@ -0,0 +1,193 @@
+use crate::bits::BitVec;
+use crate::{Allocator, IdvStorage, RedirectIndex};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use specs::world::Index;
+
+/// An internal invariant violation found by [`IdvStorage::audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditError {
+    /// The redirect of `index` points to a data slot that is missing or empty.
+    DanglingRedirect { index: Index, slot: usize },
+    /// Two redirects point to the same data slot.
+    SharedSlot { slot: usize },
+    /// A slot on the free list holds a component.
+    OccupiedFreeSlot { slot: usize },
+    /// A slot appears on the free list more than once, or is out of bounds.
+    InvalidFreeSlot { slot: usize },
+    /// A slot vacated by a component marked for removal is occupied, out of
+    /// bounds, marked twice or also referenced or free.
+    InvalidTombstone { slot: usize },
+    /// A data slot is neither referenced by a redirect nor on the free list.
+    LeakedSlot { slot: usize },
+    /// The tracked length does not match the number of stored components.
+    LengthMismatch { len: usize, occupied: usize },
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AuditError::DanglingRedirect { index, slot } => write!(
+                f,
+                "index {} redirects to data slot {} which holds no component",
+                index, slot
+            ),
+            AuditError::SharedSlot { slot } => {
+                write!(f, "data slot {} is referenced by more than one index", slot)
+            }
+            AuditError::OccupiedFreeSlot { slot } => {
+                write!(f, "data slot {} is on the free list but occupied", slot)
+            }
+            AuditError::InvalidFreeSlot { slot } => {
+                write!(f, "free list entry {} is duplicated or out of bounds", slot)
+            }
+            AuditError::InvalidTombstone { slot } => {
+                write!(
+                    f,
+                    "data slot {} is marked for removal but not pending",
+                    slot
+                )
+            }
+            AuditError::LeakedSlot { slot } => {
+                write!(f, "data slot {} is neither occupied nor free", slot)
+            }
+            AuditError::LengthMismatch { len, occupied } => write!(
+                f,
+                "length is {} but {} components are stored",
+                len, occupied
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AuditError {}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Checks the internal invariants of the storage, returning the first
+    /// violation found. Meant for tests and fuzzing.
+    pub fn audit(&self) -> Result<(), AuditError> {
+        let mut referenced = BitVec::new();
+        let mut occupied = 0;
+
+        for (i, group) in self.redirects.iter().enumerate() {
+            for (j, &redirect) in group.iter().enumerate() {
+                if redirect == I::EMPTY {
+                    continue;
+                }
+
+                let slot = redirect.to_usize();
+
+                if self.data.get(slot).is_none() {
+                    return Err(AuditError::DanglingRedirect {
+                        index: (i * SPARSE_RATIO + j) as Index,
+                        slot,
+                    });
+                }
+
+                if referenced.add(slot) {
+                    return Err(AuditError::SharedSlot { slot });
+                }
+
+                occupied += 1;
+            }
+        }
+
+        if occupied != self.len {
+            return Err(AuditError::LengthMismatch {
+                len: self.len,
+                occupied,
+            });
+        }
+
+        let mut free = BitVec::new();
+
+        for &slot in self.free_slots.iter() {
+            let slot = slot.to_usize();
+
+            if slot >= self.data.len() || free.add(slot) {
+                return Err(AuditError::InvalidFreeSlot { slot });
+            }
+
+            if referenced.contains(slot) || self.data.get(slot).is_some() {
+                return Err(AuditError::OccupiedFreeSlot { slot });
+            }
+        }
+
+        for &(slot, _) in self.tombstones.iter() {
+            let slot = slot.to_usize();
+
+            if slot >= self.data.len()
+                || self.data.get(slot).is_some()
+                || referenced.contains(slot)
+                || free.add(slot)
+            {
+                return Err(AuditError::InvalidTombstone { slot });
+            }
+        }
+
+        for slot in 0..self.data.len() {
+            if !referenced.contains(slot) && !free.contains(slot) {
+                return Err(AuditError::LeakedSlot { slot });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the redirect groups and the data slot occupancy as text, for
+    /// diagnosing a corrupted storage.
+    pub fn dump_layout(&self) -> String {
+        let mut out = String::new();
+        // Writing to a `String` never fails.
+        let _ = self.write_layout(&mut out);
+        out
+    }
+
+    fn write_layout(&self, out: &mut String) -> fmt::Result {
+        for (i, group) in self.redirects.iter().enumerate() {
+            write!(out, "group {} [", i)?;
+
+            for (j, &redirect) in group.iter().enumerate() {
+                if j > 0 {
+                    out.push_str(", ");
+                }
+
+                if redirect == I::EMPTY {
+                    out.push('-');
+                } else {
+                    write!(out, "{}", redirect.to_usize())?;
+                }
+            }
+
+            out.push_str("]\n");
+        }
+
+        out.push_str("slots ");
+
+        for slot in 0..self.data.len() {
+            out.push(if self.data.get(slot).is_some() {
+                '#'
+            } else {
+                '.'
+            });
+        }
+
+        write!(
+            out,
+            "\nfree {:?}",
+            self.free_slots_iter().collect::<Vec<_>>()
+        )?;
+
+        if !self.tombstones.is_empty() {
+            let marked: Vec<_> = self.tombstones.iter().map(|s| s.0.to_usize()).collect();
+            write!(out, "\nmarked {:?}", marked)?;
+        }
+
+        out.push('\n');
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{allocator, Allocator, Global};
+use alloc::collections::TryReserveError;
+use core::mem;
+
+const WORD_BITS: usize = usize::BITS as usize;
+
+/// A growable bit set that, unlike specs' `BitSet`, holds any `usize`.
+pub(crate) struct BitVec<A: Allocator + Clone = Global> {
+    words: alloc_vec!(usize, A),
+    alloc: A,
+}
+
+impl<A: Allocator + Clone> Clone for BitVec<A> {
+    fn clone(&self) -> Self {
+        BitVec {
+            words: self.words.clone(),
+            alloc: self.alloc.clone(),
+        }
+    }
+}
+
+impl BitVec {
+    pub(crate) fn new() -> Self {
+        BitVec::new_in(Global)
+    }
+}
+
+impl<A: Allocator + Clone> BitVec<A> {
+    pub(crate) fn new_in(alloc: A) -> Self {
+        BitVec {
+            words: allocator::new_vec(&alloc),
+            alloc,
+        }
+    }
+
+    /// Creates a set that holds the values below `bits` without
+    /// reallocating.
+    pub(crate) fn with_capacity_in(bits: usize, alloc: A) -> Self {
+        BitVec {
+            words: allocator::vec_with_capacity(bits.div_ceil(WORD_BITS), &alloc),
+            alloc,
+        }
+    }
+
+    pub(crate) fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Removes every value, passing each to `f` in ascending order, even if
+    /// `f` panics. Keeps the allocation.
+    pub(crate) fn clear_with(&mut self, mut f: impl FnMut(usize)) {
+        struct Zero<'a>(&'a mut [usize]);
+
+        impl Drop for Zero<'_> {
+            fn drop(&mut self) {
+                self.0.fill(0);
+            }
+        }
+
+        let words = Zero(&mut self.words);
+
+        for k in 0..words.0.len() {
+            let mut rest = mem::take(&mut words.0[k]);
+
+            while rest != 0 {
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                f(k * WORD_BITS + bit);
+            }
+        }
+
+        drop(words);
+        self.words.clear();
+    }
+
+    /// Bytes allocated for the words.
+    pub(crate) fn allocated_bytes(&self) -> usize {
+        self.words.capacity() * mem::size_of::<usize>()
+    }
+
+    /// Makes room for the values below `bits`, so adding them does not
+    /// allocate.
+    pub(crate) fn reserve(&mut self, bits: usize) {
+        let words = bits.div_ceil(WORD_BITS);
+        self.words.reserve(words.saturating_sub(self.words.len()));
+    }
+
+    /// Like [`reserve`](Self::reserve), but returns an error if the
+    /// allocation fails.
+    pub(crate) fn try_reserve(&mut self, bits: usize) -> Result<(), TryReserveError> {
+        let words = bits.div_ceil(WORD_BITS);
+        self.words
+            .try_reserve(words.saturating_sub(self.words.len()))
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        // Trailing zero words carry no information.
+        let used = self
+            .words
+            .iter()
+            .rposition(|&word| word != 0)
+            .map_or(0, |last| last + 1);
+        self.words.truncate(used);
+        self.words.shrink_to_fit();
+    }
+
+    #[inline]
+    pub(crate) fn contains(&self, i: usize) -> bool {
+        match self.words.get(i / WORD_BITS) {
+            Some(&word) => word & (1 << (i % WORD_BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Adds `i`, returning `true` if it was already present.
+    #[inline]
+    pub(crate) fn add(&mut self, i: usize) -> bool {
+        let word = i / WORD_BITS;
+
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        let bit = 1 << (i % WORD_BITS);
+        let present = self.words[word] & bit != 0;
+        self.words[word] |= bit;
+        present
+    }
+
+    /// Removes `i`, returning `true` if it was present.
+    #[inline]
+    pub(crate) fn remove(&mut self, i: usize) -> bool {
+        match self.words.get_mut(i / WORD_BITS) {
+            Some(word) => {
+                let bit = 1 << (i % WORD_BITS);
+                let present = *word & bit != 0;
+                *word &= !bit;
+                present
+            }
+            None => false,
+        }
+    }
+
+    /// Iterates the values in ascending order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(k, &word)| {
+            let mut rest = word;
+
+            core::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(k * WORD_BITS + bit)
+            })
+        })
+    }
+}
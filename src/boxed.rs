@@ -0,0 +1,129 @@
+use crate::{IdvStorage, MemoryStats, RedirectIndex};
+use alloc::boxed::Box;
+use specs::hibitset::BitSetLike;
+use specs::storage::{DistinctStorage, UnprotectedStorage};
+use specs::world::Index;
+
+/// An [`IdvStorage`] that keeps every component in its own heap allocation,
+/// so vacant slots only cost a pointer.
+pub struct BoxedIdvStorage<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    storage: IdvStorage<Box<T>, SPARSE_RATIO, I>,
+}
+
+impl<T, const SPARSE_RATIO: usize, I> Default for BoxedIdvStorage<T, SPARSE_RATIO, I> {
+    fn default() -> Self {
+        BoxedIdvStorage {
+            storage: IdvStorage::default(),
+        }
+    }
+}
+
+impl<T: Clone, const SPARSE_RATIO: usize, I: Clone> Clone for BoxedIdvStorage<T, SPARSE_RATIO, I> {
+    fn clone(&self) -> Self {
+        BoxedIdvStorage {
+            storage: self.storage.clone(),
+        }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> BoxedIdvStorage<T, SPARSE_RATIO, I> {
+    /// Returns the number of components stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.storage.contains(idx)
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        self.storage.get_checked(idx).map(|v| &**v)
+    }
+
+    /// Returns a mutable reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked_mut(&mut self, idx: Index) -> Option<&mut T> {
+        self.storage.get_checked_mut(idx).map(|v| &mut **v)
+    }
+
+    /// Stores `v` at `idx`, reusing the allocation, and returns the previous
+    /// component. Panics if the internal slot space is exhausted.
+    pub fn replace(&mut self, idx: Index, v: T) -> Option<T> {
+        match self.storage.get_checked_mut(idx) {
+            Some(old) => Some(core::mem::replace(&mut **old, v)),
+            None => {
+                self.storage.replace(idx, Box::new(v));
+                None
+            }
+        }
+    }
+
+    /// Removes and returns the component at `idx`, if any.
+    pub fn take(&mut self, idx: Index) -> Option<T> {
+        self.storage.take(idx).map(|v| *v)
+    }
+
+    /// Drops every component while keeping the data slots for reuse.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+    }
+
+    /// Returns the number of data slots allocated, see
+    /// [`IdvStorage::capacity`].
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.storage.capacity()
+    }
+
+    /// Reports how much memory the storage has allocated, not counting the
+    /// heap allocations of the components themselves.
+    pub fn memory_usage(&self) -> MemoryStats {
+        self.storage.memory_usage()
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> UnprotectedStorage<T>
+    for BoxedIdvStorage<T, SPARSE_RATIO, I>
+{
+    unsafe fn clean<B>(&mut self, has: B)
+    where
+        B: BitSetLike,
+    {
+        UnprotectedStorage::clean(&mut self.storage, has);
+    }
+
+    #[inline]
+    unsafe fn get(&self, idx: Index) -> &T {
+        UnprotectedStorage::get(&self.storage, idx)
+    }
+
+    #[inline]
+    unsafe fn get_mut(&mut self, idx: Index) -> &mut T {
+        UnprotectedStorage::get_mut(&mut self.storage, idx)
+    }
+
+    unsafe fn insert(&mut self, idx: Index, v: T) {
+        self.replace(idx, v);
+    }
+
+    unsafe fn remove(&mut self, idx: Index) -> T {
+        *UnprotectedStorage::remove(&mut self.storage, idx)
+    }
+}
+
+// Same slot layout as the wrapped storage.
+unsafe impl<T, const SPARSE_RATIO: usize, I> DistinctStorage
+    for BoxedIdvStorage<T, SPARSE_RATIO, I>
+{
+}
@@ -0,0 +1,75 @@
+use crate::{IdvStorage, RedirectIndex, SlotPolicy, MIN_GROWTH};
+use core::marker::PhantomData;
+
+/// Configures an [`IdvStorage`] before creating it, returned by
+/// [`IdvStorage::builder`].
+pub struct IdvStorageBuilder<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    capacity: usize,
+    min_growth: usize,
+    policy: SlotPolicy,
+    marker: PhantomData<fn() -> (T, I)>,
+}
+
+impl<T, const SPARSE_RATIO: usize, I> Default for IdvStorageBuilder<T, SPARSE_RATIO, I> {
+    fn default() -> Self {
+        IdvStorageBuilder {
+            capacity: 0,
+            min_growth: MIN_GROWTH,
+            policy: SlotPolicy::Lifo,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorage<T, SPARSE_RATIO, I> {
+    /// Returns a builder for a storage with non-default settings.
+    pub fn builder() -> IdvStorageBuilder<T, SPARSE_RATIO, I> {
+        IdvStorageBuilder::default()
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorageBuilder<T, SPARSE_RATIO, I> {
+    /// Creates a builder with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Preallocates room for every index below `max_index`, like
+    /// [`IdvStorage::with_capacity`].
+    pub fn capacity(mut self, max_index: usize) -> Self {
+        self.capacity = max_index;
+        self
+    }
+
+    /// Sets the fewest data slots added at once when the free slots run out,
+    /// `8` by default.
+    pub fn growth(mut self, min_slots: usize) -> Self {
+        self.min_growth = min_slots.max(1);
+        self
+    }
+
+    /// Hands out the lowest free slot first instead of the most recently
+    /// freed one, see [`SlotPolicy::LowestFirst`].
+    pub fn lowest_first(mut self, lowest_first: bool) -> Self {
+        self.policy = if lowest_first {
+            SlotPolicy::LowestFirst
+        } else {
+            SlotPolicy::Lifo
+        };
+        self
+    }
+
+    /// Sets which free slot new components are placed in.
+    pub fn slot_policy(mut self, policy: SlotPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Creates the configured, empty storage.
+    pub fn build(self) -> IdvStorage<T, SPARSE_RATIO, I> {
+        let mut storage = IdvStorage::with_capacity(self.capacity);
+        storage.policy = self.policy;
+        storage.min_growth = self.min_growth;
+        storage
+    }
+}
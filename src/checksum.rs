@@ -0,0 +1,86 @@
+use crate::{Allocator, IdvStorage, RedirectIndex};
+use core::hash::{Hash, Hasher};
+
+impl<T: Hash, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Returns a checksum of the `(index, value)` pairs that is independent of
+    /// the layout and, given a portable `Hash` for `T`, of the platform.
+    pub fn content_checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The 64 bit FNV-1a hash, fed little endian bytes with `usize` and
+/// `isize` widened to 64 bits.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write(&[n]);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i8(&mut self, n: i8) {
+        self.write_u8(n as u8);
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u16(n as u16);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u32(n as u32);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i128(&mut self, n: i128) {
+        self.write_u128(n as u128);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        // Sign extended, so negative values match between 32 and 64 bits.
+        self.write_i64(n as i64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{Allocator, Global, IdvStorage, RedirectIndex};
+use specs::world::Index;
+
+/// A view into a single index of an [`IdvStorage`], returned by
+/// [`IdvStorage::entry`].
+pub enum Entry<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    Occupied(OccupiedEntry<'a, T, SPARSE_RATIO, I, A>),
+    Vacant(VacantEntry<'a, T, SPARSE_RATIO, I, A>),
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    Entry<'a, T, SPARSE_RATIO, I, A>
+{
+    /// Returns the index of this entry.
+    pub fn key(&self) -> Index {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `v` if the entry is vacant and returns a reference to the component.
+    pub fn or_insert(self, v: T) -> &'a mut T {
+        self.or_insert_with(|| v)
+    }
+
+    /// Inserts the result of `f` if the entry is vacant and returns a reference
+    /// to the component.
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut T
+    where
+        F: FnOnce() -> T,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Inserts `T::default()` if the entry is vacant and returns a reference
+    /// to the component.
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    /// Calls `f` on the component if the entry is occupied.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut T),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+/// An occupied entry of an [`IdvStorage`].
+pub struct OccupiedEntry<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    pub(crate) storage: &'a mut IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) idx: Index,
+    pub(crate) internal: usize,
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    OccupiedEntry<'a, T, SPARSE_RATIO, I, A>
+{
+    pub fn key(&self) -> Index {
+        self.idx
+    }
+
+    pub fn get(&self) -> &T {
+        self.storage.data.get(self.internal).unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.storage.data.get_mut(self.internal).unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        self.storage.data.get_mut(self.internal).unwrap()
+    }
+
+    /// Replaces the component, returning the old one.
+    pub fn insert(&mut self, v: T) -> T {
+        count_op!(self.storage, overwrites);
+        core::mem::replace(self.get_mut(), v)
+    }
+
+    /// Removes the component from the storage.
+    pub fn remove(self) -> T {
+        unsafe { self.storage.c_remove(self.idx as usize).unwrap() }
+    }
+}
+
+/// A vacant entry of an [`IdvStorage`].
+pub struct VacantEntry<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    pub(crate) storage: &'a mut IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) idx: Index,
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    VacantEntry<'a, T, SPARSE_RATIO, I, A>
+{
+    pub fn key(&self) -> Index {
+        self.idx
+    }
+
+    /// Inserts `v` and returns a reference to it. Panics if the internal slot
+    /// space is exhausted.
+    pub fn insert(self, v: T) -> &'a mut T {
+        let internal =
+            unsafe { self.storage.insert_vacant(self.idx as usize, v) }.unwrap_or_else(|_| {
+                panic!("storage cannot hold more than {} components", I::MAX_SLOTS)
+            });
+
+        self.storage.data.get_mut(internal).unwrap()
+    }
+}
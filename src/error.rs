@@ -0,0 +1,68 @@
+use crate::{AuditError, CapacityError, InsertError};
+use alloc::collections::TryReserveError;
+use core::fmt;
+use specs::world::Index;
+
+/// Any failure reported by the fallible methods of this crate. The insert
+/// errors convert into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdvError {
+    /// Every internal slot is in use.
+    CapacityExhausted,
+    /// Growing the storage failed to allocate.
+    AllocFailed,
+    /// The index lies past what the storage or its input accepts.
+    OutOfRange(Index),
+    /// An internal invariant is broken, as found by
+    /// [`IdvStorage::audit`](crate::IdvStorage::audit).
+    InvariantViolated(AuditError),
+}
+
+impl fmt::Display for IdvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdvError::CapacityExhausted => write!(f, "storage has no free internal slots left"),
+            IdvError::AllocFailed => write!(f, "failed to allocate room for a component"),
+            IdvError::OutOfRange(idx) => write!(f, "index {} is out of range", idx),
+            IdvError::InvariantViolated(err) => write!(f, "storage invariant violated: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IdvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IdvError::InvariantViolated(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<CapacityError<T>> for IdvError {
+    fn from(_: CapacityError<T>) -> Self {
+        IdvError::CapacityExhausted
+    }
+}
+
+impl<T> From<InsertError<T>> for IdvError {
+    fn from(err: InsertError<T>) -> Self {
+        match err {
+            InsertError::Capacity(_) => IdvError::CapacityExhausted,
+            InsertError::Alloc(_) => IdvError::AllocFailed,
+            InsertError::OutOfRange(idx, _) => IdvError::OutOfRange(idx),
+        }
+    }
+}
+
+impl From<AuditError> for IdvError {
+    fn from(err: AuditError) -> Self {
+        IdvError::InvariantViolated(err)
+    }
+}
+
+impl From<TryReserveError> for IdvError {
+    fn from(_: TryReserveError) -> Self {
+        IdvError::AllocFailed
+    }
+}
@@ -0,0 +1,191 @@
+use crate::redirect;
+use crate::{missing_component, missing_removal, InsertError, RedirectIndex};
+use core::mem::{self, MaybeUninit};
+use specs::hibitset::BitSetLike;
+use specs::storage::{DistinctStorage, UnprotectedStorage};
+use specs::world::Index;
+
+/// An interleaved storage for the indices `0..N` in fixed-size arrays, with
+/// one `I` redirect per index in `[I; N]`. It never allocates itself.
+pub struct FixedIdvStorage<T, const N: usize, I: RedirectIndex = u16> {
+    redirects: [I; N],
+    data: [MaybeUninit<T>; N],
+    free_slots: [I; N],
+    free_len: usize,
+    /// Slots from here on have never been used and are not on the free list.
+    fresh: usize,
+    len: usize,
+}
+
+impl<T, const N: usize, I: RedirectIndex> Default for FixedIdvStorage<T, N, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, I: RedirectIndex> FixedIdvStorage<T, N, I> {
+    /// Creates an empty storage in a `const` context. Panics if `N` exceeds the
+    /// slots `I` can address.
+    pub const fn new() -> Self {
+        assert!(
+            N <= I::MAX_SLOTS,
+            "FixedIdvStorage cannot address this many slots with its index type"
+        );
+
+        FixedIdvStorage {
+            redirects: [I::EMPTY; N],
+            // SAFETY: an array of `MaybeUninit` needs no initialization.
+            data: unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() },
+            free_slots: [I::EMPTY; N],
+            free_len: 0,
+            fresh: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of components stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of indices the storage can hold, `N`.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.lookup(idx).is_some()
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        let internal = self.lookup(idx)?;
+        Some(unsafe { self.data[internal].assume_init_ref() })
+    }
+
+    /// Returns a mutable reference to the component at `idx`, if any.
+    pub fn get_checked_mut(&mut self, idx: Index) -> Option<&mut T> {
+        let internal = self.lookup(idx)?;
+        Some(unsafe { self.data[internal].assume_init_mut() })
+    }
+
+    /// Stores `v` at `idx` and returns the previous component. Panics if `idx`
+    /// is not below `N`.
+    pub fn replace(&mut self, idx: Index, v: T) -> Option<T> {
+        match self.try_replace(idx, v) {
+            Ok(old) => old,
+            Err(_) => panic!("index {} is out of bounds for {} slots", idx, N),
+        }
+    }
+
+    /// Inserts a value, returning it back if `idx` is not below `N`.
+    pub fn try_insert(&mut self, idx: Index, v: T) -> Result<(), InsertError<T>> {
+        self.try_replace(idx, v).map(drop)
+    }
+
+    /// Removes and returns the component at `idx`, if any.
+    pub fn take(&mut self, idx: Index) -> Option<T> {
+        let internal = redirect::vacate(&mut self.redirects, idx as usize)?;
+
+        self.free_slots[self.free_len] = internal;
+        self.free_len += 1;
+        self.len -= 1;
+
+        Some(unsafe { self.data[internal.to_usize()].assume_init_read() })
+    }
+
+    /// Removes every component.
+    pub fn clear(&mut self) {
+        for idx in 0..N {
+            self.take(idx as Index);
+        }
+    }
+
+    fn try_replace(&mut self, idx: Index, v: T) -> Result<Option<T>, InsertError<T>> {
+        let i = idx as usize;
+
+        if i >= N {
+            return Err(InsertError::OutOfRange(idx, v));
+        }
+
+        if let Some(internal) = self.lookup(idx) {
+            let slot = unsafe { self.data[internal].assume_init_mut() };
+            return Ok(Some(mem::replace(slot, v)));
+        }
+
+        // Every index below `N` fits and there are `N` slots, so a vacant
+        // index always finds a free or fresh slot.
+        let internal = if self.free_len > 0 {
+            self.free_len -= 1;
+            self.free_slots[self.free_len].to_usize()
+        } else {
+            self.fresh += 1;
+            self.fresh - 1
+        };
+
+        self.data[internal].write(v);
+        redirect::occupy(&mut self.redirects, i, internal);
+        self.len += 1;
+
+        Ok(None)
+    }
+
+    #[inline]
+    fn lookup(&self, idx: Index) -> Option<usize> {
+        redirect::lookup(&self.redirects, idx as usize)
+    }
+}
+
+impl<T, const N: usize, I: RedirectIndex> Drop for FixedIdvStorage<T, N, I> {
+    fn drop(&mut self) {
+        if mem::needs_drop::<T>() {
+            self.clear();
+        }
+    }
+}
+
+impl<T, const N: usize, I: RedirectIndex> UnprotectedStorage<T> for FixedIdvStorage<T, N, I> {
+    unsafe fn clean<B>(&mut self, has: B)
+    where
+        B: BitSetLike,
+    {
+        for idx in has.iter() {
+            if idx as usize >= N {
+                break;
+            }
+
+            self.take(idx);
+        }
+    }
+
+    unsafe fn get(&self, idx: Index) -> &T {
+        self.get_checked(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    unsafe fn get_mut(&mut self, idx: Index) -> &mut T {
+        self.get_checked_mut(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    unsafe fn insert(&mut self, idx: Index, v: T) {
+        self.replace(idx, v);
+    }
+
+    unsafe fn remove(&mut self, idx: Index) -> T {
+        self.take(idx).unwrap_or_else(|| missing_removal(idx))
+    }
+}
+
+// Every index redirects to its own slot.
+unsafe impl<T, const N: usize, I: RedirectIndex> DistinctStorage for FixedIdvStorage<T, N, I> {}
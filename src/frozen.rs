@@ -0,0 +1,84 @@
+use crate::{IdvStorage, Iter, MemoryStats, RedirectIndex};
+use core::ops;
+use specs::hibitset::BitSetLike;
+use specs::world::Index;
+
+/// A read-only, compacted [`IdvStorage`] returned by [`IdvStorage::freeze`].
+/// Use [`thaw`](Self::thaw) to modify it again.
+#[derive(Clone)]
+pub struct FrozenIdvStorage<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    storage: IdvStorage<T, SPARSE_RATIO, I>,
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorage<T, SPARSE_RATIO, I> {
+    /// Compacts the storage and turns it into a read-only
+    /// [`FrozenIdvStorage`].
+    pub fn freeze(mut self) -> FrozenIdvStorage<T, SPARSE_RATIO, I> {
+        self.shrink_to_fit();
+        FrozenIdvStorage { storage: self }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> FrozenIdvStorage<T, SPARSE_RATIO, I> {
+    /// Turns the storage back into a modifiable [`IdvStorage`].
+    pub fn thaw(self) -> IdvStorage<T, SPARSE_RATIO, I> {
+        self.storage
+    }
+
+    /// Returns the number of components stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.storage.contains(idx)
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        self.storage.get_checked(idx)
+    }
+
+    /// Iterates the components at the indices set in `mask`.
+    pub fn iter<'a, B>(&'a self, mask: &'a B) -> Iter<'a, B, T, SPARSE_RATIO, I>
+    where
+        B: BitSetLike,
+    {
+        self.storage.iter(mask)
+    }
+
+    /// Iterates every component, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.storage.values()
+    }
+
+    /// Iterates the occupied indices in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = Index> + '_ {
+        self.storage.keys()
+    }
+
+    /// Reports how much memory the storage has allocated.
+    pub fn memory_usage(&self) -> MemoryStats {
+        self.storage.memory_usage()
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> ops::Index<Index>
+    for FrozenIdvStorage<T, SPARSE_RATIO, I>
+{
+    type Output = T;
+
+    fn index(&self, idx: Index) -> &T {
+        &self.storage[idx]
+    }
+}
@@ -0,0 +1,550 @@
+use crate::slots::Slot;
+use crate::{Allocator, Global, IdvStorage, RedirectIndex};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use specs::hibitset::BitSetLike;
+use specs::world::Index;
+
+const BITS: usize = usize::BITS.trailing_zeros() as usize;
+const MASK: usize = (1 << BITS) - 1;
+const LAYERS: usize = 4;
+const MAX_BITS: usize = 1 << (BITS * LAYERS);
+
+/// With the `prefetch` feature on x86, starts loading the data slot `idx`
+/// redirects to.
+#[inline(always)]
+fn prefetch<T, const SPARSE_RATIO: usize, I: RedirectIndex>(
+    redirects: &[[I; SPARSE_RATIO]],
+    data: *const Slot<T>,
+    idx: usize,
+) {
+    #[cfg(all(
+        feature = "prefetch",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    ))]
+    if let Some(group) = redirects.get(idx / SPARSE_RATIO) {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+        let internal = group[idx % SPARSE_RATIO];
+
+        if internal != I::EMPTY {
+            // SAFETY: SSE is available, and a prefetch never faults.
+            unsafe {
+                _mm_prefetch::<_MM_HINT_T0>(data.wrapping_add(internal.to_usize()) as *const i8);
+            }
+        }
+    }
+
+    #[cfg(not(all(
+        feature = "prefetch",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    )))]
+    let _ = (redirects, data, idx);
+}
+
+fn layer<B: BitSetLike>(set: &B, level: usize, i: usize) -> usize {
+    match level {
+        0 => set.layer0(i),
+        1 => set.layer1(i),
+        2 => set.layer2(i),
+        _ => set.layer3(),
+    }
+}
+
+/// Returns the lowest set bit at or above `pos`, walking up the layers to
+/// skip empty words and back down into the first non-empty one.
+pub(crate) fn next_bit<B: BitSetLike>(set: &B, mut pos: usize) -> Option<usize> {
+    let mut level = 0;
+
+    while pos < MAX_BITS {
+        let shift = BITS * level;
+        let word = pos >> (shift + BITS);
+        let w = layer(set, level, word) & (!0 << ((pos >> shift) & MASK));
+
+        if w == 0 {
+            if level == LAYERS - 1 {
+                return None;
+            }
+
+            pos = (word + 1) << (shift + BITS);
+            level += 1;
+            continue;
+        }
+
+        let child = (word << BITS) | w.trailing_zeros() as usize;
+        pos = pos.max(child << shift);
+
+        if level == 0 {
+            return Some(pos);
+        }
+
+        level -= 1;
+    }
+
+    None
+}
+
+/// Returns the highest set bit at or below `pos`, the mirror of [`next_bit`].
+fn prev_bit<B: BitSetLike>(set: &B, pos: usize) -> Option<usize> {
+    let mut pos = pos.min(MAX_BITS - 1);
+    let mut level = 0;
+
+    loop {
+        let shift = BITS * level;
+        let word = pos >> (shift + BITS);
+        let w = layer(set, level, word) & (!0 >> (MASK - ((pos >> shift) & MASK)));
+
+        if w == 0 {
+            if level == LAYERS - 1 || word == 0 {
+                return None;
+            }
+
+            pos = (word << (shift + BITS)) - 1;
+            level += 1;
+            continue;
+        }
+
+        let child = (word << BITS) | (MASK - w.leading_zeros() as usize);
+        pos = pos.min(((child + 1) << shift) - 1);
+
+        if level == 0 {
+            return Some(pos);
+        }
+
+        level -= 1;
+    }
+}
+
+/// Returns the highest set bit of `mask`, if any.
+pub(crate) fn highest_bit<B: BitSetLike>(mask: &B) -> Option<usize> {
+    prev_bit(mask, usize::MAX)
+}
+
+/// Walks the set bits of a mask in `front..back` from both ends.
+pub(crate) struct MaskCursor<'a, B> {
+    mask: &'a B,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, B: BitSetLike> MaskCursor<'a, B> {
+    /// Nothing at or past `end` is yielded.
+    pub(crate) fn new(mask: &'a B, end: usize) -> Self {
+        MaskCursor {
+            mask,
+            front: 0,
+            back: end,
+        }
+    }
+
+    fn next(&mut self) -> Option<Index> {
+        match next_bit(self.mask, self.front) {
+            Some(idx) if idx < self.back => {
+                self.front = idx + 1;
+                Some(idx as Index)
+            }
+            _ => {
+                self.front = self.back;
+                None
+            }
+        }
+    }
+
+    fn next_back(&mut self) -> Option<Index> {
+        if self.back <= self.front {
+            return None;
+        }
+
+        match prev_bit(self.mask, self.back - 1) {
+            Some(idx) if idx >= self.front => {
+                self.back = idx;
+                Some(idx as Index)
+            }
+            _ => {
+                self.back = self.front;
+                None
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`IdvStorage::iter`], skipping set bits without a
+/// component.
+pub struct Iter<'a, B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    pub(crate) storage: &'a IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) bits: MaskCursor<'a, B>,
+}
+
+impl<'a, B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Iterator
+    for Iter<'a, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+    type Item = (Index, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(idx) = self.bits.next() {
+            if let Some(v) = self.storage.get_checked(idx) {
+                let storage = self.storage;
+                prefetch(
+                    &storage.redirects,
+                    storage.data.as_ptr(),
+                    (idx as usize).wrapping_add(1),
+                );
+                return Some((idx, v));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.storage.len))
+    }
+}
+
+impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> DoubleEndedIterator
+    for Iter<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(idx) = self.bits.next_back() {
+            if let Some(v) = self.storage.get_checked(idx) {
+                let storage = self.storage;
+                prefetch(
+                    &storage.redirects,
+                    storage.data.as_ptr(),
+                    (idx as usize).wrapping_sub(1),
+                );
+                return Some((idx, v));
+            }
+        }
+
+        None
+    }
+}
+
+impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> FusedIterator
+    for Iter<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+}
+
+/// Mutable iterator returned by [`IdvStorage::iter_mut`].
+pub struct IterMut<'a, B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    pub(crate) redirects: &'a [[I; SPARSE_RATIO]],
+    pub(crate) data: *mut Slot<T>,
+    pub(crate) bits: MaskCursor<'a, B>,
+    pub(crate) len: usize,
+    pub(crate) marker: PhantomData<&'a mut IdvStorage<T, SPARSE_RATIO, I, A>>,
+}
+
+unsafe impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Send
+    for IterMut<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: Sync,
+    T: Send,
+    I: Send,
+{
+}
+
+unsafe impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Sync
+    for IterMut<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: Sync,
+    T: Sync,
+    I: Sync,
+{
+}
+
+impl<'a, B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone>
+    IterMut<'a, B, T, SPARSE_RATIO, I, A>
+where
+    I: RedirectIndex,
+{
+    fn resolve(&mut self, idx: Index) -> Option<&'a mut T> {
+        let internal =
+            self.redirects.get(idx as usize / SPARSE_RATIO)?[idx as usize % SPARSE_RATIO];
+
+        if internal == I::EMPTY {
+            return None;
+        }
+
+        // SAFETY: a redirected slot always holds a value. The cursor yields
+        // every index at most once and distinct indices never share an
+        // internal slot, so no two references handed out by this iterator
+        // alias.
+        Some(unsafe { &mut *(*self.data.add(internal.to_usize())).0.as_mut_ptr() })
+    }
+}
+
+impl<'a, B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Iterator
+    for IterMut<'a, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+    type Item = (Index, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(idx) = self.bits.next() {
+            if let Some(v) = self.resolve(idx) {
+                prefetch(self.redirects, self.data, (idx as usize).wrapping_add(1));
+                return Some((idx, v));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len))
+    }
+}
+
+impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> DoubleEndedIterator
+    for IterMut<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while let Some(idx) = self.bits.next_back() {
+            if let Some(v) = self.resolve(idx) {
+                prefetch(self.redirects, self.data, (idx as usize).wrapping_sub(1));
+                return Some((idx, v));
+            }
+        }
+
+        None
+    }
+}
+
+impl<B, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> FusedIterator
+    for IterMut<'_, B, T, SPARSE_RATIO, I, A>
+where
+    B: BitSetLike,
+    I: RedirectIndex,
+{
+}
+
+/// Draining iterator returned by [`IdvStorage::drain`], in ascending index
+/// order. Dropping it drops the rest.
+pub struct Drain<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone = Global>
+{
+    pub(crate) storage: &'a mut IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) next_idx: usize,
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Iterator
+    for Drain<'_, T, SPARSE_RATIO, I, A>
+{
+    type Item = (Index, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.storage.remove_next(&mut self.next_idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.storage.len, Some(self.storage.len))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> ExactSizeIterator
+    for Drain<'_, T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> FusedIterator
+    for Drain<'_, T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Drop
+    for Drain<'_, T, SPARSE_RATIO, I, A>
+{
+    fn drop(&mut self) {
+        self.storage.clear();
+    }
+}
+
+/// Iterator returned by [`IdvStorage::drain_filter`]. Dropping it still
+/// removes the remaining accepted components.
+pub struct DrainFilter<
+    'a,
+    T,
+    const SPARSE_RATIO: usize,
+    I: RedirectIndex,
+    F,
+    A: Allocator + Clone = Global,
+> where
+    F: FnMut(Index, &mut T) -> bool,
+{
+    pub(crate) storage: &'a mut IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) next_idx: usize,
+    pub(crate) pred: F,
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, F, A: Allocator + Clone> Iterator
+    for DrainFilter<'_, T, SPARSE_RATIO, I, F, A>
+where
+    F: FnMut(Index, &mut T) -> bool,
+{
+    type Item = (Index, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Removals may trim trailing groups, so the end is rechecked on
+        // every step.
+        while self.next_idx < self.storage.index_end() {
+            let idx = self.next_idx;
+            self.next_idx += 1;
+
+            let remove = match self.storage.get_checked_mut(idx as Index) {
+                Some(v) => (self.pred)(idx as Index, v),
+                None => false,
+            };
+
+            if remove {
+                let v = unsafe { self.storage.c_remove_deferred(idx)? };
+                return Some((idx as Index, v));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.storage.len))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, F, A: Allocator + Clone> FusedIterator
+    for DrainFilter<'_, T, SPARSE_RATIO, I, F, A>
+where
+    F: FnMut(Index, &mut T) -> bool,
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, F, A: Allocator + Clone> Drop
+    for DrainFilter<'_, T, SPARSE_RATIO, I, F, A>
+where
+    F: FnMut(Index, &mut T) -> bool,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.storage.finish_removals();
+    }
+}
+
+/// Consuming iterator returned by [`IdvStorage::into_iter`], in ascending
+/// index order.
+pub struct IntoIter<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    pub(crate) storage: IdvStorage<T, SPARSE_RATIO, I, A>,
+    pub(crate) next_idx: usize,
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Iterator
+    for IntoIter<T, SPARSE_RATIO, I, A>
+{
+    type Item = (Index, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.storage.remove_next(&mut self.next_idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.storage.len, Some(self.storage.len))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> ExactSizeIterator
+    for IntoIter<T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> FusedIterator
+    for IntoIter<T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> IntoIterator
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    type Item = (Index, T);
+    type IntoIter = IntoIter<T, SPARSE_RATIO, I, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            storage: self,
+            next_idx: 0,
+        }
+    }
+}
+
+/// Iterator over `&IdvStorage`, yielding every component with its index in
+/// ascending index order.
+pub struct Entries<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone = Global> {
+    storage: &'a IdvStorage<T, SPARSE_RATIO, I, A>,
+    next_idx: usize,
+    remaining: usize,
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Iterator
+    for Entries<'a, T, SPARSE_RATIO, I, A>
+{
+    type Item = (Index, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Stopping at the last component skips the empty tail of the table.
+        while self.remaining > 0 {
+            let idx = self.next_idx;
+            self.next_idx += 1;
+
+            if let Some(v) = self.storage.get_checked(idx as Index) {
+                self.remaining -= 1;
+                return Some((idx as Index, v));
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> ExactSizeIterator
+    for Entries<'_, T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> FusedIterator
+    for Entries<'_, T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> IntoIterator
+    for &'a IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    type Item = (Index, &'a T);
+    type IntoIter = Entries<'a, T, SPARSE_RATIO, I, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Entries {
+            storage: self,
+            next_idx: 0,
+            remaining: self.len,
+        }
+    }
+}
@@ -1,124 +1,2092 @@
-use specs::hibitset::BitSetLike;
-use specs::storage::{DistinctStorage, UnprotectedStorage};
+//! Interleaved dense vec storage for specs.
+//!
+//! The crate itself only needs `alloc` and builds as `no_std` when the default
+//! `std` feature is disabled. Note that specs still depends on std.
+//!
+//! Optional features:
+//! - `cache-aligned` puts every data slot on its own 64 byte cache line.
+//! - `prefetch` prefetches the next data slot during masked iteration on x86.
+//! - `checked` checks the `UnprotectedStorage` accessors in release builds.
+//! - `stats` counts inserts, removals, cleans and grows per storage.
+//! - `generations` counts removals per index for generational handles.
+//! - `smallvec` keeps short free lists inline.
+//! - `log` warns about fragmentation.
+//! - `serde` and `zeroize` add the corresponding impls and methods.
+//! - `allocator_api` accepts any `Allocator` on nightly.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::TryReserveError;
+use alloc::vec::Vec;
+use core::array;
+use core::cmp::Reverse;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops;
+use core::panic::{RefUnwindSafe, UnwindSafe};
+use slots::{Slot, Slots};
+use specs::hibitset::{BitSet, BitSetLike};
+use specs::storage::{DistinctStorage, FlaggedStorage, UnprotectedStorage};
 use specs::world::Index;
 
-const SPARSE_RATIO: usize = 4;
+/// Bumps an [`OpStats`] counter of `$storage`, compiled out without the
+/// `stats` feature.
+macro_rules! count_op {
+    ($storage:expr, $counter:ident) => {
+        count_op!($storage, $counter, 1)
+    };
+    ($storage:expr, $counter:ident, $n:expr) => {
+        #[cfg(feature = "stats")]
+        {
+            $storage.stats.$counter += $n as u64;
+        }
+    };
+}
+
+/// A buffer allocated through the allocator `$a`: `Vec<$t, $a>` with the
+/// `allocator_api` feature, a plain `Vec<$t>` without it.
+#[cfg(feature = "allocator_api")]
+macro_rules! alloc_vec {
+    ($t:ty, $a:ty) => {
+        alloc::vec::Vec<$t, $a>
+    };
+}
+
+#[cfg(not(feature = "allocator_api"))]
+macro_rules! alloc_vec {
+    ($t:ty, $a:ty) => {
+        alloc::vec::Vec<$t>
+    };
+}
+
+/// The free slot list of a storage allocating through `$a`.
+#[cfg(not(feature = "smallvec"))]
+macro_rules! free_slots {
+    ($i:ty, $a:ty) => {
+        alloc_vec!($i, $a)
+    };
+}
+
+/// Keeps up to 16 free slots inline. A longer list moves to the global
+/// allocator, not the one of the storage.
+#[cfg(feature = "smallvec")]
+macro_rules! free_slots {
+    ($i:ty, $a:ty) => {
+        smallvec::SmallVec<[$i; 16]>
+    };
+}
+
+mod adaptive;
+mod allocator;
+mod audit;
+mod bits;
+mod boxed;
+mod builder;
+mod checksum;
+mod entry;
+mod error;
+mod fixed;
+mod frozen;
+mod iter;
+mod paged;
+#[cfg(feature = "parallel")]
+mod par_iter;
+mod redirect;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod slots;
+mod snapshot;
+mod view;
+
+pub use adaptive::AdaptiveStorage;
+pub use allocator::{Allocator, Global};
+pub use audit::AuditError;
+pub use boxed::BoxedIdvStorage;
+pub use builder::IdvStorageBuilder;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::IdvError;
+pub use fixed::FixedIdvStorage;
+pub use frozen::FrozenIdvStorage;
+use iter::MaskCursor;
+pub use iter::{Drain, DrainFilter, Entries, IntoIter, Iter, IterMut};
+pub use paged::{PagedIdvStorage, PAGE_LEN};
+#[cfg(feature = "parallel")]
+pub use par_iter::ParIterMut;
+pub use snapshot::Snapshot;
+pub use view::ReadView;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Integer type used to address internal data slots. Its maximum value marks
+/// an empty redirect.
+pub trait RedirectIndex: sealed::Sealed + Copy + Eq {
+    const EMPTY: Self;
+    const MAX_SLOTS: usize;
+
+    fn from_usize(v: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_redirect_index {
+    ($($ty:ty),*) => {
+        $(
+            impl sealed::Sealed for $ty {}
+
+            impl RedirectIndex for $ty {
+                const EMPTY: Self = <$ty>::MAX;
+                const MAX_SLOTS: usize = <$ty>::MAX as usize;
+
+                #[inline]
+                fn from_usize(v: usize) -> Self {
+                    v as $ty
+                }
+
+                #[inline]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_redirect_index!(u16, u32);
+
+#[cfg(not(feature = "smallvec"))]
+fn free_slots_with_capacity<I, A: Allocator + Clone>(
+    capacity: usize,
+    alloc: &A,
+) -> free_slots!(I, A) {
+    allocator::vec_with_capacity(capacity, alloc)
+}
+
+#[cfg(feature = "smallvec")]
+fn free_slots_with_capacity<I, A: Allocator + Clone>(capacity: usize, _: &A) -> free_slots!(I, A) {
+    smallvec::SmallVec::with_capacity(capacity)
+}
+
+/// Fewest data slots added at once when a storage runs out of free slots.
+const MIN_GROWTH: usize = 8;
+
+/// Removals between two fragmentation checks with the `log` feature.
+#[cfg(feature = "log")]
+const FRAGMENTATION_CHECK_INTERVAL: u32 = 64;
+
+/// State of [`IdvStorage::warn_on_fragmentation`].
+#[cfg(feature = "log")]
+#[derive(Clone, Copy)]
+struct FragmentationWarning {
+    limit: f32,
+    countdown: u32,
+    /// Cleared after warning, set again once fragmentation drops back to the
+    /// limit, so a fragmented storage warns once rather than on every check.
+    armed: bool,
+}
+
+/// Panics for an `UnprotectedStorage` access to an index without a
+/// component, which means the caller's mask does not match the storage.
+#[cold]
+pub(crate) fn missing_component(idx: Index) -> ! {
+    panic!(
+        "no component at index {}, mask and storage out of sync?",
+        idx
+    )
+}
+
+/// Like [`missing_component`], for removals.
+#[cold]
+pub(crate) fn missing_removal(idx: Index) -> ! {
+    panic!(
+        "no component to remove at index {}, removed twice or mask and storage out of sync?",
+        idx
+    )
+}
+
+/// Largest `SPARSE_RATIO` a storage accepts. Creating a storage with a ratio
+/// of zero or above it fails to build:
+///
+/// ```compile_fail,E0080
+/// let storage = specs_idvs::IdvStorage::<u32, 1025>::default();
+/// ```
+///
+/// ```compile_fail,E0080
+/// let storage = specs_idvs::IdvStorage::<u32, 0>::with_capacity(10);
+/// ```
+///
+/// ```
+/// let storage = specs_idvs::IdvStorage::<u32, 1024>::default();
+/// ```
+pub const MAX_SPARSE_RATIO: usize = 1024;
+
+/// One past the highest index a specs `BitSet` can hold: 2^24 on 64 bit
+/// targets and 2^20 on 32 bit ones.
+pub const MASK_INDEX_LIMIT: usize = BitSet::BITS_PER_USIZE.pow(4);
+
+/// Called with the new number of data slots whenever a storage grows.
+type GrowObserver = Box<dyn FnMut(usize) + Send + Sync + UnwindSafe>;
+
+/// Returned when every internal slot is in use, handing the value back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityError<T>(pub T);
+
+impl<T> CapacityError<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage has no free internal slots left")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> std::error::Error for CapacityError<T> {}
+
+/// Returned by [`IdvStorage::checked_insert`] when a value cannot be stored,
+/// handing it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertError<T> {
+    /// Every internal slot is in use.
+    Capacity(T),
+    /// Growing the storage failed to allocate.
+    Alloc(T),
+    /// The index lies past what the storage can address.
+    OutOfRange(Index, T),
+}
+
+impl<T> InsertError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            InsertError::Capacity(v) | InsertError::Alloc(v) | InsertError::OutOfRange(_, v) => v,
+        }
+    }
+}
+
+impl<T> From<CapacityError<T>> for InsertError<T> {
+    fn from(err: CapacityError<T>) -> Self {
+        InsertError::Capacity(err.0)
+    }
+}
+
+impl<T> fmt::Display for InsertError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::Capacity(_) => write!(f, "storage has no free internal slots left"),
+            InsertError::Alloc(_) => write!(f, "failed to allocate room for a component"),
+            InsertError::OutOfRange(idx, _) => write!(f, "index {} is out of range", idx),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> std::error::Error for InsertError<T> {}
+
+/// Which free internal slot an [`IdvStorage`] hands out to a new component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlotPolicy {
+    /// Reuse the most recently freed slot. Freeing and reusing a slot is
+    /// `O(1)`.
+    #[default]
+    Lifo,
+    /// Reuse the lowest free slot, so the data stays dense. Freeing a slot
+    /// costs `O(free slots)`, bulk removals sort the free list once.
+    LowestFirst,
+}
+
+/// Where an incremental clean through [`IdvStorage::clean_chunked`] stands.
+/// Use a fresh cursor to abandon a pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CleanCursor {
+    next: usize,
+    cleaned: usize,
+}
+
+impl CleanCursor {
+    /// Creates a cursor at the start of a pass.
+    pub fn new() -> Self {
+        CleanCursor::default()
+    }
+
+    /// Returns how many components the current pass has removed, or the last
+    /// pass once it is done.
+    pub fn cleaned(&self) -> usize {
+        self.cleaned
+    }
+}
+
+/// Whether [`IdvStorage::clean_chunked`] is done with its mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanProgress {
+    /// Every index of the mask has been cleaned.
+    Done,
+    /// More indices remain, call `clean_chunked` again with the same mask
+    /// and cursor.
+    Pending,
+}
+
+impl CleanProgress {
+    /// Returns `true` if the mask has been cleaned completely.
+    pub fn is_done(self) -> bool {
+        self == CleanProgress::Done
+    }
+}
+
+/// Cumulative operation counts of an [`IdvStorage`], as returned by
+/// [`IdvStorage::op_stats`].
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OpStats {
+    /// Components stored at a previously empty index.
+    pub inserts: u64,
+    /// Components stored over an existing one.
+    pub overwrites: u64,
+    /// Components removed, including by `clean` and `clear`.
+    pub removes: u64,
+    /// Calls to `clean`.
+    pub cleans: u64,
+    /// Times the data slots were grown.
+    pub grows: u64,
+}
+
+/// Memory consumption of an [`IdvStorage`], as returned by
+/// [`IdvStorage::memory_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Bytes allocated for redirect groups.
+    pub redirect_bytes: usize,
+    /// Bytes allocated for data slots.
+    pub data_bytes: usize,
+    /// Bytes allocated for the free slot list.
+    pub free_list_bytes: usize,
+    /// Bytes allocated for the bits marking occupied data slots.
+    pub occupancy_bytes: usize,
+    /// Number of data slots holding a component.
+    pub occupied_slots: usize,
+    /// Number of data slots available for reuse.
+    pub free_slots: usize,
+}
+
+impl MemoryStats {
+    /// Returns the total number of bytes allocated by the storage.
+    pub fn total_bytes(&self) -> usize {
+        self.redirect_bytes + self.data_bytes + self.free_list_bytes + self.occupancy_bytes
+    }
+}
+
+/// Interleaved dense vec storage. Redirects cover every index up to the
+/// highest one inserted, [`PagedIdvStorage`] suits sparse high indices.
+pub struct IdvStorage<T, const SPARSE_RATIO: usize = 4, I = u16, A: Allocator + Clone = Global> {
+    redirects: alloc_vec!([I; SPARSE_RATIO], A),
+    data: Slots<T, A>,
+    free_slots: free_slots!(I, A),
+    /// Components marked for removal along with the slot they vacated, which
+    /// stays off the free list until the next sweep.
+    tombstones: alloc_vec!((I, T), A),
+    len: usize,
+    policy: SlotPolicy,
+    min_growth: usize,
+    #[cfg(feature = "stats")]
+    stats: OpStats,
+    #[cfg(feature = "log")]
+    fragmentation_warning: Option<FragmentationWarning>,
+    /// Removal counts of the indices up to the highest one removed so far,
+    /// see [`IdvStorage::generation`].
+    #[cfg(feature = "generations")]
+    generations: alloc_vec!(u32, A),
+    on_grow: Option<GrowObserver>,
+}
+
+/// An [`IdvStorage`] with the default ratio and index type, for specs'
+/// `#[storage(Idv)]` derive attribute.
+pub type Idv<T> = IdvStorage<T>;
+
+/// An [`IdvStorage`] wrapped in specs' [`FlaggedStorage`], emitting a
+/// `ComponentEvent` for every insertion, mutable access and removal.
+pub type FlaggedIdvStorage<T, const SPARSE_RATIO: usize = 4, I = u16> =
+    FlaggedStorage<T, IdvStorage<T, SPARSE_RATIO, I>>;
+
+impl<T: Clone, const SPARSE_RATIO: usize, I: Clone, A: Allocator + Clone> Clone
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn clone(&self) -> Self {
+        IdvStorage {
+            redirects: self.redirects.clone(),
+            data: self.data.clone(),
+            free_slots: self.free_slots.clone(),
+            tombstones: self.tombstones.clone(),
+            len: self.len,
+            policy: self.policy,
+            min_growth: self.min_growth,
+            #[cfg(feature = "stats")]
+            stats: self.stats,
+            #[cfg(feature = "log")]
+            fragmentation_warning: self.fragmentation_warning,
+            #[cfg(feature = "generations")]
+            generations: self.generations.clone(),
+            on_grow: None,
+        }
+    }
+
+    /// Reuses the allocations of `self`, overwriting components in place. If
+    /// cloning a component panics, `self` is left empty.
+    fn clone_from(&mut self, source: &Self) {
+        // Handles into the old contents must not survive an unwind below,
+        // which leaves `self` empty with its own generations.
+        #[cfg(feature = "generations")]
+        self.bump_all_generations();
+        let guard = ClearOnUnwind(self);
+        let this = &mut *guard.0;
+        this.redirects.clone_from(&source.redirects);
+        this.data.clone_from(&source.data);
+        this.free_slots.clone_from(&source.free_slots);
+        this.tombstones.clone_from(&source.tombstones);
+        this.len = source.len;
+        this.policy = source.policy;
+        this.min_growth = source.min_growth;
+        #[cfg(feature = "stats")]
+        {
+            this.stats = source.stats;
+        }
+        #[cfg(feature = "log")]
+        {
+            this.fragmentation_warning = source.fragmentation_warning;
+        }
+        #[cfg(feature = "generations")]
+        this.generations.clone_from(&source.generations);
+        mem::forget(guard);
+    }
+}
+
+/// Empties a storage when dropped, for code that runs user callbacks while
+/// the storage is half rebuilt. Forgotten once the rebuild finished.
+struct ClearOnUnwind<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone>(
+    &'a mut IdvStorage<T, SPARSE_RATIO, I, A>,
+);
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Drop
+    for ClearOnUnwind<'_, T, SPARSE_RATIO, I, A>
+{
+    fn drop(&mut self) {
+        let storage = &mut *self.0;
+        storage.redirects.clear();
+        storage.free_slots.clear();
+        storage.len = 0;
+        storage.data.clear();
+        storage.tombstones.clear();
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> IdvStorage<T, SPARSE_RATIO, I, A> {
+    /// Referenced by every constructor, so an invalid ratio fails to build.
+    const VALID_RATIO: () = assert!(
+        SPARSE_RATIO > 0 && SPARSE_RATIO <= MAX_SPARSE_RATIO,
+        "SPARSE_RATIO must be between 1 and MAX_SPARSE_RATIO"
+    );
+
+    /// Counts a removal from every index covered by a redirect group, which
+    /// includes all occupied ones.
+    #[cfg(feature = "generations")]
+    fn bump_all_generations(&mut self) {
+        let end = self.redirects.len() * SPARSE_RATIO;
+
+        if self.generations.len() < end {
+            self.generations.resize(end, 0);
+        }
+
+        for gen in &mut self.generations[..end] {
+            *gen = gen.wrapping_add(1);
+        }
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone + Default> Default
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn default() -> Self {
+        IdvStorage::new_in(A::default())
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> IdvStorage<T, SPARSE_RATIO, I, A> {
+    /// Creates an empty storage that allocates all of its buffers through
+    /// `alloc`, nothing before the first insert.
+    pub fn new_in(alloc: A) -> Self {
+        let () = Self::VALID_RATIO;
+
+        IdvStorage {
+            redirects: allocator::new_vec(&alloc),
+            free_slots: free_slots_with_capacity(0, &alloc),
+            tombstones: allocator::new_vec(&alloc),
+            #[cfg(feature = "generations")]
+            generations: allocator::new_vec(&alloc),
+            data: Slots::new_in(alloc),
+            len: 0,
+            policy: SlotPolicy::Lifo,
+            min_growth: MIN_GROWTH,
+            #[cfg(feature = "stats")]
+            stats: OpStats::default(),
+            #[cfg(feature = "log")]
+            fragmentation_warning: None,
+            on_grow: None,
+        }
+    }
+
+    /// Returns the allocator the storage allocates its buffers through.
+    pub fn allocator(&self) -> &A {
+        self.data.allocator()
+    }
+}
+
+/// Storages are equal if they hold equal components at the same indices,
+/// regardless of which internal slots the components live in.
+impl<T: PartialEq, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> PartialEq
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.occupied().eq(other.occupied())
+    }
+}
+
+impl<T: Eq, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Eq
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+}
+
+/// Hashes the `(index, value)` pairs in ascending index order, consistent with
+/// `PartialEq`.
+impl<T: Hash, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Hash
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+
+        for (idx, v) in self.occupied() {
+            idx.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
+// `UnwindSafe` is derived. The grow observer is not `RefUnwindSafe`, but a
+// shared reference cannot reach it.
+impl<T, const SPARSE_RATIO: usize, I, A> RefUnwindSafe for IdvStorage<T, SPARSE_RATIO, I, A>
+where
+    T: RefUnwindSafe,
+    I: RefUnwindSafe,
+    A: Allocator + Clone + RefUnwindSafe,
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorage<T, SPARSE_RATIO, I> {
+    /// Creates a storage that can hold a component for every index below
+    /// `max_index` without reallocating.
+    pub fn with_capacity(max_index: usize) -> Self {
+        IdvStorage::with_capacity_in(max_index, Global)
+    }
+
+    /// Builds a storage from a flat vector such as [`to_dense`](Self::to_dense)
+    /// returns. Panics if the internal slots cannot address every component.
+    pub fn from_dense(dense: Vec<Option<T>>) -> Self {
+        let mut storage = IdvStorage::default();
+        storage
+            .redirects
+            .reserve(dense.len().div_ceil(SPARSE_RATIO));
+        storage.reserve(dense.iter().filter(|v| v.is_some()).count());
+        storage.insert_many(
+            dense
+                .into_iter()
+                .enumerate()
+                .filter_map(|(idx, v)| Some((idx as Index, v?))),
+        );
+        storage
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Number of logical indices covered by each redirect group.
+    pub const SPARSE_RATIO: usize = SPARSE_RATIO;
+
+    /// Most components the storage can hold at once, set by the redirect
+    /// index type `I`.
+    pub const MAX_INTERNAL_SLOTS: usize = I::MAX_SLOTS;
+
+    /// Like [`with_capacity`](IdvStorage::with_capacity), but allocates
+    /// through `alloc`.
+    pub fn with_capacity_in(max_index: usize, alloc: A) -> Self {
+        let slots = max_index.min(I::MAX_SLOTS);
+        let mut storage = IdvStorage::new_in(alloc);
+        storage.redirects.reserve(max_index.div_ceil(SPARSE_RATIO));
+        storage.data.reserve(slots);
+        storage.free_slots.reserve(slots);
+        storage
+    }
+
+    /// Reserves data slots and redirect groups for `additional_indices` more
+    /// components past the current indices.
+    pub fn reserve(&mut self, additional_indices: usize) {
+        let slots = additional_indices
+            .saturating_sub(self.free_slots.len())
+            .min(I::MAX_SLOTS - self.data.len());
+        self.redirects
+            .reserve(additional_indices.div_ceil(SPARSE_RATIO));
+        self.data.reserve(slots);
+        self.free_slots.reserve(slots);
+    }
+
+    /// Reserves redirect groups up to the highest bit set in `mask` and data
+    /// slots for as many components as it has bits set.
+    pub fn reserve_from_mask<B>(&mut self, mask: &B)
+    where
+        B: BitSetLike,
+    {
+        if let Some(highest) = iter::highest_bit(mask) {
+            let required = highest / SPARSE_RATIO + 1;
+            self.redirects
+                .reserve(required.saturating_sub(self.redirects.len()));
+        }
+
+        self.reserve(mask.iter().count());
+    }
+
+    /// Like [`reserve`](Self::reserve), but returns an error instead of
+    /// aborting if the allocation fails.
+    pub fn try_reserve(&mut self, additional_indices: usize) -> Result<(), IdvError> {
+        let slots = additional_indices
+            .saturating_sub(self.free_slots.len())
+            .min(I::MAX_SLOTS - self.data.len());
+        self.redirects
+            .try_reserve(additional_indices.div_ceil(SPARSE_RATIO))?;
+        self.data.try_reserve(slots)?;
+        self.try_reserve_free(slots)?;
+        Ok(())
+    }
+
+    /// Returns the policy used to pick free internal slots.
+    pub fn slot_policy(&self) -> SlotPolicy {
+        self.policy
+    }
+
+    /// Changes the policy used to pick free internal slots from now on.
+    pub fn set_slot_policy(&mut self, policy: SlotPolicy) {
+        let resort = policy == SlotPolicy::LowestFirst && self.policy != policy;
+        self.policy = policy;
+
+        if resort {
+            self.sort_free_slots();
+        }
+    }
+
+    /// Calls `f` with the new number of data slots whenever the storage grows.
+    /// The observer is not carried over by `clone`.
+    pub fn on_grow<F>(&mut self, f: F)
+    where
+        F: FnMut(usize) + Send + Sync + UnwindSafe + 'static,
+    {
+        self.on_grow = Some(Box::new(f));
+    }
+
+    /// Logs a warning once [`fragmentation`](Self::fragmentation) exceeds
+    /// `limit`, checked every 64 removals.
+    #[cfg(feature = "log")]
+    pub fn warn_on_fragmentation(&mut self, limit: f32) {
+        self.fragmentation_warning = Some(FragmentationWarning {
+            limit,
+            countdown: FRAGMENTATION_CHECK_INTERVAL,
+            armed: true,
+        });
+    }
+
+    /// Creates the redirect group covering `idx` without storing anything. The
+    /// next removal may trim it again.
+    pub fn ensure_index(&mut self, idx: Index) {
+        self.check_prefill(idx as usize);
+    }
+
+    /// Returns the number of components currently stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of components the data slots can hold without
+    /// reallocating. Redirects may still have to grow.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.data.capacity().min(I::MAX_SLOTS)
+    }
+
+    /// Returns the operation counters since creation or the last reset.
+    #[cfg(feature = "stats")]
+    pub fn op_stats(&self) -> OpStats {
+        self.stats
+    }
+
+    /// Sets all operation counters back to zero.
+    #[cfg(feature = "stats")]
+    pub fn reset_op_stats(&mut self) {
+        self.stats = OpStats::default();
+    }
+
+    /// Reports how much memory the storage has allocated and how many of its
+    /// data slots are in use.
+    pub fn memory_usage(&self) -> MemoryStats {
+        MemoryStats {
+            redirect_bytes: self.redirects.capacity() * mem::size_of::<[I; SPARSE_RATIO]>(),
+            data_bytes: self.data.capacity() * mem::size_of::<Slot<T>>(),
+            free_list_bytes: self.free_slots.capacity() * mem::size_of::<I>(),
+            occupancy_bytes: self.data.occupancy_bytes(),
+            occupied_slots: self.len,
+            free_slots: self.free_slots.len(),
+        }
+    }
+
+    /// Returns the number of redirect groups allocated and how many of them
+    /// cover at least one component.
+    pub fn group_stats(&self) -> (usize, usize) {
+        let used = self
+            .redirects
+            .iter()
+            .filter(|group| group.iter().any(|&redirect| redirect != I::EMPTY))
+            .count();
+
+        (self.redirects.len(), used)
+    }
+
+    /// Returns the share of allocated data slots holding a component, `0.0`
+    /// for a storage without any.
+    pub fn occupancy_ratio(&self) -> f32 {
+        match self.capacity() {
+            0 => 0.0,
+            capacity => self.len as f32 / capacity as f32,
+        }
+    }
+
+    /// Returns how scattered the components are across the internal slots,
+    /// from `0.0` for packed slots up to nearly `1.0`.
+    pub fn fragmentation(&self) -> f32 {
+        let highest = self
+            .redirects
+            .iter()
+            .flatten()
+            .filter(|&&r| r != I::EMPTY)
+            .map(|r| r.to_usize())
+            .max();
+
+        match highest {
+            Some(highest) => 1.0 - self.len as f32 / (highest + 1) as f32,
+            None => 0.0,
+        }
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.lookup(idx as usize).is_some()
+    }
+
+    /// Returns the internal data slot `idx` currently redirects to, for
+    /// debugging.
+    #[inline]
+    pub fn internal_slot(&self, idx: Index) -> Option<usize> {
+        self.lookup(idx as usize)
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        let internal = self.lookup(idx as usize)?;
+        self.data.get(internal)
+    }
+
+    /// Returns a reference to the component at `idx` without checking that
+    /// there is one.
+    ///
+    /// # Safety
+    ///
+    /// `idx` must hold a component.
+    #[inline]
+    pub unsafe fn get_unchecked_ref(&self, idx: Index) -> &T {
+        let internal = self.resolve_to_internal(idx as usize);
+        debug_assert!(internal != I::EMPTY, "no component at index {}", idx);
+        self.data.get_unchecked(internal.to_usize())
+    }
+
+    /// Returns every component as one slice if the indices `0..len()` all live
+    /// in the internal slot of the same number, `None` otherwise.
+    pub fn as_contiguous_slice(&self) -> Option<&[T]> {
+        if (0..self.len).any(|idx| self.lookup(idx) != Some(idx)) {
+            return None;
+        }
+
+        // SAFETY: every slot below `len` is referenced by a redirect, so it
+        // holds a component.
+        unsafe { self.data.prefix_slice(self.len) }
+    }
+
+    /// Looks up every index in `idxs` and appends the results to `out` in the
+    /// same order.
+    pub fn batch_get<'a>(&'a self, idxs: &[Index], out: &mut Vec<Option<&'a T>>) {
+        out.reserve(idxs.len());
+        out.extend(idxs.iter().map(|&idx| self.get_checked(idx)));
+    }
+
+    /// Looks up `N` indices at once, which may repeat, `None` for indices
+    /// without a component.
+    pub fn get_array<const N: usize>(&self, idxs: [Index; N]) -> [Option<&T>; N] {
+        array::from_fn(|k| self.get_checked(idxs[k]))
+    }
+
+    /// Returns a mutable reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked_mut(&mut self, idx: Index) -> Option<&mut T> {
+        let internal = self.lookup(idx as usize)?;
+        self.data.get_mut(internal)
+    }
+
+    /// Returns a mutable reference to the part of the component at `idx` that
+    /// `f` picks, if there is a component.
+    #[inline]
+    pub fn project_mut<U, F>(&mut self, idx: Index, f: F) -> Option<&mut U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        self.get_checked_mut(idx).map(f)
+    }
+
+    /// Returns a raw pointer to the component at `idx`, if any.
+    ///
+    /// # Safety
+    ///
+    /// The pointer is only valid until the storage is next modified.
+    #[inline]
+    pub unsafe fn as_ptr(&self, idx: Index) -> Option<*const T> {
+        self.get_checked(idx).map(|v| v as *const T)
+    }
+
+    /// Returns a raw mutable pointer to the component at `idx`, if any.
+    ///
+    /// # Safety
+    ///
+    /// As [`as_ptr`](Self::as_ptr), and no other reference may be alive.
+    #[inline]
+    pub unsafe fn as_mut_ptr(&mut self, idx: Index) -> Option<*mut T> {
+        self.get_checked_mut(idx).map(|v| v as *mut T)
+    }
+
+    /// Returns mutable references to the components at `a` and `b`, `None` if
+    /// they are equal or either is vacant.
+    pub fn get_two_mut(&mut self, a: Index, b: Index) -> Option<(&mut T, &mut T)> {
+        let [a, b] = self.get_disjoint_mut([a, b])?;
+        Some((a, b))
+    }
+
+    /// Returns mutable references to the components at `N` indices, `None` if
+    /// any repeats or is vacant.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, idxs: [Index; N]) -> Option<[&mut T; N]> {
+        let mut internals = [0; N];
+
+        for (k, &idx) in idxs.iter().enumerate() {
+            if idxs[..k].contains(&idx) {
+                return None;
+            }
+
+            internals[k] = self.lookup(idx as usize)?;
+        }
+
+        let data = self.data.as_mut_ptr();
+
+        // SAFETY: every slot is occupied, and distinct indices never share an
+        // internal slot, so the references do not alias.
+        Some(array::from_fn(|k| unsafe {
+            &mut *(*data.add(internals[k])).0.as_mut_ptr()
+        }))
+    }
+
+    /// Returns the generation of the component at `idx`, the number of
+    /// removals from that index, if there is a component.
+    #[cfg(feature = "generations")]
+    pub fn generation(&self, idx: Index) -> Option<u32> {
+        self.lookup(idx as usize)?;
+        Some(self.generation_of(idx as usize))
+    }
+
+    /// Returns a reference to the component at `idx` if its generation is
+    /// still `gen`.
+    #[cfg(feature = "generations")]
+    pub fn get_generational(&self, idx: Index, gen: u32) -> Option<&T> {
+        if self.generation_of(idx as usize) == gen {
+            self.get_checked(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the component at `idx` if its generation
+    /// is still `gen`.
+    #[cfg(feature = "generations")]
+    pub fn get_generational_mut(&mut self, idx: Index, gen: u32) -> Option<&mut T> {
+        if self.generation_of(idx as usize) == gen {
+            self.get_checked_mut(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Drops every component while keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        count_op!(self, removes, self.len);
+        #[cfg(feature = "generations")]
+        self.bump_all_generations();
+        let empty = allocator::new_vec(self.allocator());
+        let mut tombstones = mem::replace(&mut self.tombstones, empty);
+        self.redirects.clear();
+        self.free_slots.clear();
+        self.len = 0;
+
+        // The storage is empty before any destructor runs, so a panicking
+        // one leaks the remaining components instead of leaving redirects
+        // to dropped slots behind.
+        self.data.clear();
+        tombstones.clear();
+        self.tombstones = tombstones;
+    }
+
+    /// Empties the storage like [`clear`](Self::clear), but hands every
+    /// component to `f` along with its index.
+    pub fn clear_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Index, T),
+    {
+        for (idx, v) in self.drain() {
+            f(idx, v);
+        }
+    }
+
+    /// Drops every component at an index of `max_index` or above, along with
+    /// the redirect groups that only cover such indices.
+    pub fn truncate(&mut self, max_index: usize) {
+        for idx in max_index..self.index_end() {
+            if self.lookup(idx).is_some() {
+                unsafe {
+                    self.c_remove_deferred(idx);
+                }
+            }
+        }
+
+        self.finish_removals();
+        self.redirects.truncate(max_index.div_ceil(SPARSE_RATIO));
+    }
+
+    /// Moves every component into the internal slots `0..len()`, leaving the
+    /// free slots as one contiguous tail.
+    pub fn compact(&mut self) {
+        self.sweep();
+        self.pack_below(self.len);
+        self.free_slots
+            .extend((self.len..self.data.len()).rev().map(I::from_usize));
+    }
+
+    /// Moves the components so that ascending indices live in ascending
+    /// internal slots `0..len()`.
+    pub fn sort_by_index(&mut self) {
+        self.sweep();
+
+        // Which index owns each slot, so a displaced component can have its
+        // redirect updated.
+        let mut owner = allocator::vec_with_capacity(self.data.len(), self.allocator());
+        owner.resize(self.data.len(), Index::MAX);
+
+        for (idx, &redirect) in self.redirects.iter().flatten().enumerate() {
+            if redirect != I::EMPTY {
+                owner[redirect.to_usize()] = idx as Index;
+            }
+        }
+
+        let mut target = 0;
+
+        for idx in 0..self.index_end() {
+            let slot = match self.lookup(idx) {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            if slot != target {
+                let displaced = owner[target];
+                let v = self.data.take(slot).expect("redirect to an empty slot");
+
+                if let Some(other) = self.data.take(target) {
+                    self.data.insert(slot, other);
+                    let d = displaced as usize;
+                    self.redirects[d / SPARSE_RATIO][d % SPARSE_RATIO] = I::from_usize(slot);
+                }
+
+                self.data.insert(target, v);
+                self.redirects[idx / SPARSE_RATIO][idx % SPARSE_RATIO] = I::from_usize(target);
+                owner[slot] = displaced;
+                owner[target] = idx as Index;
+            }
+
+            target += 1;
+        }
+
+        self.free_slots.clear();
+        self.free_slots
+            .extend((self.len..self.data.len()).rev().map(I::from_usize));
+    }
+
+    /// Moves the components down and releases the data slots and redirect
+    /// groups they no longer need.
+    pub fn shrink_to_fit(&mut self) {
+        self.sweep();
+
+        let groups = self
+            .redirects
+            .iter()
+            .rposition(|group| group.iter().any(|&r| r != I::EMPTY))
+            .map_or(0, |last| last + 1);
+
+        self.pack_below(self.len);
+        self.redirects.truncate(groups);
+        self.redirects.shrink_to_fit();
+        self.data.truncate(self.len);
+        self.data.shrink_to_fit();
+        self.free_slots.shrink_to_fit();
+    }
+
+    /// Releases the spare capacity of the free slot list without moving any
+    /// components.
+    pub fn shrink_free_list(&mut self) {
+        self.free_slots.shrink_to_fit();
+    }
+
+    /// Iterates the components at the indices set in `mask`.
+    pub fn iter<'a, B>(&'a self, mask: &'a B) -> Iter<'a, B, T, SPARSE_RATIO, I, A>
+    where
+        B: BitSetLike,
+    {
+        Iter {
+            storage: self,
+            bits: MaskCursor::new(mask, self.index_end()),
+        }
+    }
+
+    /// Mutably iterates the components at the indices set in `mask`.
+    pub fn iter_mut<'a, B>(&'a mut self, mask: &'a B) -> IterMut<'a, B, T, SPARSE_RATIO, I, A>
+    where
+        B: BitSetLike,
+    {
+        IterMut {
+            redirects: &self.redirects,
+            data: self.data.as_mut_ptr(),
+            bits: MaskCursor::new(mask, self.index_end()),
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+
+    /// Removes every component, yielding them with their index.
+    pub fn drain(&mut self) -> Drain<'_, T, SPARSE_RATIO, I, A> {
+        Drain {
+            storage: self,
+            next_idx: 0,
+        }
+    }
+
+    /// Consumes the storage, returning every component with its index in
+    /// ascending index order.
+    pub fn into_vec(self) -> Vec<(Index, T)> {
+        self.into_iter().collect()
+    }
+
+    /// Returns a flat vector holding a clone of every component at the
+    /// position of its index, up to the highest occupied one.
+    pub fn to_dense(&self) -> Vec<Option<T>>
+    where
+        T: Clone,
+    {
+        let len = self
+            .occupied()
+            .last()
+            .map_or(0, |(idx, _)| idx as usize + 1);
+        let mut dense = Vec::new();
+        dense.resize_with(len, || None);
+
+        for (idx, v) in self.occupied() {
+            dense[idx as usize] = Some(v.clone());
+        }
+
+        dense
+    }
+
+    /// Consumes the storage and returns one holding `f(idx, v)` at every
+    /// occupied index.
+    pub fn map_values<U, F>(self, mut f: F) -> IdvStorage<U, SPARSE_RATIO, I, A>
+    where
+        F: FnMut(Index, T) -> U,
+    {
+        let mut out = IdvStorage::new_in(self.allocator().clone());
+        out.redirects.reserve(self.redirects.len());
+        out.reserve(self.len());
+        out.insert_many(self.into_iter().map(|(idx, v)| (idx, f(idx, v))));
+        out
+    }
+
+    /// Converts the storage to another sparse ratio, regrouping only the
+    /// redirects.
+    pub fn with_sparse_ratio<const NEW_RATIO: usize>(self) -> IdvStorage<T, NEW_RATIO, I, A> {
+        let () = IdvStorage::<T, NEW_RATIO, I>::VALID_RATIO;
+        let end = self.index_end();
+        let mut redirects = allocator::vec_with_capacity(end.div_ceil(NEW_RATIO), self.allocator());
+
+        for (idx, &redirect) in self.redirects.iter().flatten().enumerate() {
+            if idx % NEW_RATIO == 0 {
+                redirects.push([I::EMPTY; NEW_RATIO]);
+            }
+
+            redirects[idx / NEW_RATIO][idx % NEW_RATIO] = redirect;
+        }
+
+        IdvStorage {
+            redirects,
+            data: self.data,
+            free_slots: self.free_slots,
+            tombstones: self.tombstones,
+            len: self.len,
+            policy: self.policy,
+            min_growth: self.min_growth,
+            #[cfg(feature = "stats")]
+            stats: self.stats,
+            #[cfg(feature = "log")]
+            fragmentation_warning: self.fragmentation_warning,
+            #[cfg(feature = "generations")]
+            generations: self.generations,
+            on_grow: self.on_grow,
+        }
+    }
+
+    /// Removes the components for which `f` returns `true`, yielding them
+    /// with their index.
+    pub fn drain_filter<F>(&mut self, f: F) -> DrainFilter<'_, T, SPARSE_RATIO, I, F, A>
+    where
+        F: FnMut(Index, &mut T) -> bool,
+    {
+        DrainFilter {
+            storage: self,
+            next_idx: 0,
+            pred: f,
+        }
+    }
+
+    /// Removes every component for which `f` returns `false`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Index, &mut T) -> bool,
+    {
+        for idx in 0..self.index_end() {
+            let keep = match self.get_checked_mut(idx as Index) {
+                Some(v) => f(idx as Index, v),
+                None => true,
+            };
+
+            if !keep {
+                unsafe {
+                    self.c_remove_deferred(idx);
+                }
+            }
+        }
+
+        self.finish_removals();
+    }
+
+    /// Iterates every component, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.data.values()
+    }
+
+    /// Mutably iterates every component, in no particular order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.data.values_mut()
+    }
+
+    /// Mutably iterates every component along with the internal slot it
+    /// lives in, in slot order.
+    pub fn internal_iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        self.data.iter_mut()
+    }
+
+    /// Iterates every component with its index in ascending index order, the
+    /// same as iterating `&storage`.
+    pub fn sorted_iter(&self) -> Entries<'_, T, SPARSE_RATIO, I, A> {
+        self.into_iter()
+    }
+
+    /// Iterates the occupied indices in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = Index> + '_ {
+        self.occupied().map(|(idx, _)| idx)
+    }
+
+    /// Iterates the components at the indices in `range` in ascending order.
+    pub fn iter_range(&self, range: ops::Range<Index>) -> impl Iterator<Item = (Index, &T)> + '_ {
+        self.redirects_in(range).filter_map(move |(idx, redirect)| {
+            if redirect == I::EMPTY {
+                return None;
+            }
+
+            Some((idx, self.data.get(redirect.to_usize())?))
+        })
+    }
+
+    /// Returns how many indices in `range` hold a component.
+    pub fn count_in_range(&self, range: ops::Range<Index>) -> usize {
+        self.redirects_in(range)
+            .filter(|&(_, redirect)| redirect != I::EMPTY)
+            .count()
+    }
+
+    /// Returns `true` if every index in `range` holds a component. An empty
+    /// range trivially does.
+    pub fn contains_range(&self, range: ops::Range<Index>) -> bool {
+        let len = range.end.saturating_sub(range.start) as usize;
+        self.count_in_range(range) == len
+    }
+
+    /// Removes the components at every index set in `mask` and returns how
+    /// many were removed.
+    pub fn remove_mask<B>(&mut self, mask: &B) -> usize
+    where
+        B: BitSetLike,
+    {
+        let before = self.len;
+
+        for idx in mask.iter() {
+            let idx = idx as usize;
+
+            // Bits are yielded in ascending order, nothing past the last
+            // redirect group can be occupied. Removals may trim that group.
+            if idx >= self.index_end() {
+                break;
+            }
+
+            unsafe {
+                self.c_remove_deferred(idx);
+            }
+        }
+
+        self.finish_removals();
+        before - self.len
+    }
+
+    /// Removes the components at the indices set in `mask` for which `pred`
+    /// returns `true`, and returns how many were removed.
+    pub fn remove_where<B, F>(&mut self, mask: &B, mut pred: F) -> usize
+    where
+        B: BitSetLike,
+        F: FnMut(Index, &T) -> bool,
+    {
+        let before = self.len;
+
+        for idx in mask.iter() {
+            if idx as usize >= self.index_end() {
+                break;
+            }
+
+            let remove = match self.get_checked(idx) {
+                Some(v) => pred(idx, v),
+                None => false,
+            };
+
+            if remove {
+                unsafe {
+                    self.c_remove_deferred(idx as usize);
+                }
+            }
+        }
+
+        self.finish_removals();
+        before - self.len
+    }
+
+    /// Removes the components at the indices set in `mask` and returns the
+    /// indices that held one, in ascending order.
+    pub fn clean_reporting<B>(&mut self, mask: &B) -> Vec<Index>
+    where
+        B: BitSetLike,
+    {
+        count_op!(self, cleans);
+        let mut removed = Vec::new();
+
+        for idx in mask.iter() {
+            // Removals may trim the last group, see `remove_mask`.
+            if idx as usize >= self.index_end() {
+                break;
+            }
+
+            if unsafe { self.c_remove_deferred(idx as usize) }.is_some() {
+                removed.push(idx);
+            }
+        }
+
+        self.finish_removals();
+        removed
+    }
+
+    /// Removes the components at up to `max_slots` indices set in `has` per
+    /// call, a zero budget counting as one, until it returns `Done`.
+    pub fn clean_chunked<B>(
+        &mut self,
+        has: &B,
+        cursor: &mut CleanCursor,
+        max_slots: usize,
+    ) -> CleanProgress
+    where
+        B: BitSetLike,
+    {
+        if cursor.next == 0 {
+            cursor.cleaned = 0;
+        }
+
+        let mut pos = cursor.next;
 
-struct InterleavedGroup<T> {
-    redirects: [u16; SPARSE_RATIO],
-    data: Option<T>,
-}
+        for _ in 0..max_slots.max(1) {
+            match iter::next_bit(has, pos) {
+                Some(idx) if idx < self.index_end() => {
+                    if unsafe { self.c_remove_deferred(idx) }.is_some() {
+                        cursor.cleaned += 1;
+                    }
+
+                    pos = idx + 1;
+                }
+                _ => break,
+            }
+        }
+
+        self.finish_removals();
 
-impl<T> InterleavedGroup<T> {
-    const fn blank() -> Self {
-        Self {
-            redirects: [0; SPARSE_RATIO],
-            data: None,
+        match iter::next_bit(has, pos) {
+            Some(idx) if idx < self.index_end() => {
+                cursor.next = pos;
+                CleanProgress::Pending
+            }
+            _ => {
+                cursor.next = 0;
+                CleanProgress::Done
+            }
         }
     }
-}
 
-pub struct IdvStorage<T> {
-    inner: Vec<InterleavedGroup<T>>,
-    next_free_slot: usize,
-    len: usize,
-}
+    /// Marks the component at `idx` as removed. It is only dropped and its
+    /// slot reused by [`sweep`](Self::sweep).
+    pub fn mark_remove(&mut self, idx: Index) {
+        let idx = idx as usize;
 
-impl<T> Default for IdvStorage<T> {
-    fn default() -> Self {
-        IdvStorage {
-            inner: Vec::new(),
-            next_free_slot: 0,
-            len: 0,
+        if let Some(internal) = self.lookup(idx) {
+            self.redirects[idx / SPARSE_RATIO][idx % SPARSE_RATIO] = I::EMPTY;
+            #[cfg(feature = "generations")]
+            self.bump_generation(idx);
+            let v = self.data.take(internal).expect("redirect to an empty slot");
+            self.tombstones.push((I::from_usize(internal), v));
+            self.len -= 1;
+            count_op!(self, removes);
         }
     }
-}
 
-impl<T> IdvStorage<T> {
+    /// Drops every component marked through [`mark_remove`](Self::mark_remove)
+    /// and makes its internal slot available again.
+    pub fn sweep(&mut self) {
+        if self.tombstones.is_empty() {
+            return;
+        }
+
+        let empty = allocator::new_vec(self.allocator());
+        let mut tombstones = mem::replace(&mut self.tombstones, empty);
+
+        self.free_slots
+            .extend(tombstones.iter().map(|&(slot, _)| slot));
+        self.sort_free_slots();
+
+        // The slots are back on the free list before any destructor runs, a
+        // panicking one then only leaks the remaining components.
+        tombstones.clear();
+
+        // Hand the buffer back so the next round of marks does not allocate.
+        self.tombstones = tombstones;
+        self.trim_tail();
+    }
+
+    /// Iterates the internal slots on the free list, in the order they would
+    /// be handed out last to first.
+    pub fn free_slots_iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.free_slots.iter().map(|slot| slot.to_usize())
+    }
+
+    /// Returns a bitset with a bit set for every occupied index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component is stored at [`MASK_INDEX_LIMIT`] or above.
+    pub fn occupied_mask(&self) -> BitSet {
+        self.checked_occupied_mask().unwrap_or_else(|| {
+            panic!(
+                "occupied index {} does not fit in a BitSet",
+                self.highest_index().unwrap_or(0)
+            )
+        })
+    }
+
+    /// Like [`occupied_mask`](Self::occupied_mask), but returns `None` instead
+    /// of panicking.
+    pub fn checked_occupied_mask(&self) -> Option<BitSet> {
+        let end = self.highest_index().map_or(0, |highest| highest + 1);
+
+        if end > MASK_INDEX_LIMIT {
+            return None;
+        }
+
+        let mut mask = BitSet::with_capacity(end as Index);
+
+        for (idx, _) in self.occupied() {
+            mask.add(idx);
+        }
+
+        Some(mask)
+    }
+
+    /// Gets the entry at `idx` for in-place manipulation.
+    pub fn entry(&mut self, idx: Index) -> Entry<'_, T, SPARSE_RATIO, I, A> {
+        match self.lookup(idx as usize) {
+            Some(internal) => Entry::Occupied(OccupiedEntry {
+                storage: self,
+                idx,
+                internal,
+            }),
+            None => Entry::Vacant(VacantEntry { storage: self, idx }),
+        }
+    }
+
+    /// Returns the component at `idx`, inserting the result of `f` first if
+    /// there is none.
+    pub fn get_or_insert_with<F>(&mut self, idx: Index, f: F) -> &mut T
+    where
+        F: FnOnce() -> T,
+    {
+        self.entry(idx).or_insert_with(f)
+    }
+
+    /// Returns the component at `idx`, inserting `T::default()` first if
+    /// there is none.
+    pub fn get_or_default(&mut self, idx: Index) -> &mut T
+    where
+        T: Default,
+    {
+        self.entry(idx).or_default()
+    }
+
+    /// Stores `v` at `idx` and returns the previous component. Panics if the
+    /// internal slot space is exhausted.
+    pub fn replace(&mut self, idx: Index, v: T) -> Option<T> {
+        match self.entry(idx) {
+            Entry::Occupied(mut entry) => Some(entry.insert(v)),
+            Entry::Vacant(entry) => {
+                entry.insert(v);
+                None
+            }
+        }
+    }
+
+    /// Stores `T::default()` at `idx`, returning `true` if it replaced a
+    /// component. Panics if the internal slot space is exhausted.
+    pub fn insert_default(&mut self, idx: Index) -> bool
+    where
+        T: Default,
+    {
+        self.replace(idx, T::default()).is_some()
+    }
+
+    /// Removes and returns the component at `idx`, if any.
+    pub fn take(&mut self, idx: Index) -> Option<T> {
+        self.lookup(idx as usize)?;
+        unsafe { self.c_remove(idx as usize) }
+    }
+
+    /// Removes the component at `idx`, zeroing it and its slot, and returns
+    /// whether there was one.
+    #[cfg(feature = "zeroize")]
+    pub fn remove_zeroize(&mut self, idx: Index) -> bool
+    where
+        T: zeroize::Zeroize,
+    {
+        let internal = match self.lookup(idx as usize) {
+            Some(internal) => internal,
+            None => return false,
+        };
+
+        if let Some(v) = self.data.get_mut(internal) {
+            v.zeroize();
+        }
+
+        drop(self.take(idx));
+        self.data.zero_vacant(internal);
+        true
+    }
+
+    /// Redirects the component at `from` to `to`, dropping the one at `to`.
+    /// Returns `false` if `from` is vacant.
+    pub fn move_index(&mut self, from: Index, to: Index) -> bool {
+        let (from, to) = (from as usize, to as usize);
+
+        let internal = match self.lookup(from) {
+            Some(internal) => internal,
+            None => return false,
+        };
+
+        if from != to {
+            self.take(to as Index);
+            self.check_prefill(to);
+            self.redirects[from / SPARSE_RATIO][from % SPARSE_RATIO] = I::EMPTY;
+            self.redirects[to / SPARSE_RATIO][to % SPARSE_RATIO] = I::from_usize(internal);
+            #[cfg(feature = "generations")]
+            self.bump_generation(from);
+
+            // Moving the highest component down empties the groups above it.
+            self.trim_tail();
+        }
+
+        true
+    }
+
+    /// Exchanges the components at `a` and `b` by swapping their redirects.
+    /// Either or both of the indices may be empty.
+    pub fn swap(&mut self, a: Index, b: Index) {
+        let (a, b) = (a as usize, b as usize);
+
+        if self.lookup(a).is_none() && self.lookup(b).is_none() {
+            return;
+        }
+
+        self.check_prefill(a.max(b));
+        let ra = self.redirects[a / SPARSE_RATIO][a % SPARSE_RATIO];
+        let rb = self.redirects[b / SPARSE_RATIO][b % SPARSE_RATIO];
+        self.redirects[a / SPARSE_RATIO][a % SPARSE_RATIO] = rb;
+        self.redirects[b / SPARSE_RATIO][b % SPARSE_RATIO] = ra;
+
+        #[cfg(feature = "generations")]
+        if a != b {
+            for (idx, redirect) in [(a, ra), (b, rb)] {
+                if redirect != I::EMPTY {
+                    self.bump_generation(idx);
+                }
+            }
+        }
+
+        // Swapping the highest component down leaves the groups above it
+        // empty, and later removals only trim from the group they touch.
+        self.trim_tail();
+    }
+
+    /// Inserts every `(index, value)` pair, reserving by the size hint. Panics
+    /// if the internal slot space is exhausted.
+    pub fn insert_many<It>(&mut self, iter: It)
+    where
+        It: IntoIterator<Item = (Index, T)>,
+    {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+
+        for (idx, v) in iter {
+            if unsafe { self.c_insert(idx as usize, v).is_err() } {
+                panic!("storage cannot hold more than {} components", I::MAX_SLOTS);
+            }
+        }
+    }
+
+    /// Inserts clones of all components of `other`, overwriting those at the
+    /// same indices. Panics if the internal slot space is exhausted.
+    pub fn copy_from(&mut self, other: &Self)
+    where
+        T: Clone,
+    {
+        self.redirects
+            .reserve(other.redirects.len().saturating_sub(self.redirects.len()));
+        self.reserve(other.len());
+        self.insert_many(other.occupied().map(|(idx, v)| (idx, v.clone())));
+    }
+
+    /// Inserts a value, returning it back if the internal slot space is exhausted.
+    pub fn try_insert(&mut self, idx: Index, v: T) -> Result<(), CapacityError<T>> {
+        unsafe { self.c_insert(idx as usize, v).map_err(CapacityError) }
+    }
+
+    /// Like [`replace`](Self::replace), but hands `v` back instead of
+    /// panicking or aborting when it cannot be stored.
+    pub fn checked_insert(&mut self, idx: Index, v: T) -> Result<Option<T>, InsertError<T>> {
+        let idx = idx as usize;
+
+        if let Some(internal) = self.lookup(idx) {
+            count_op!(self, overwrites);
+            let slot = unsafe { self.data.get_unchecked_mut(internal) };
+            return Ok(Some(mem::replace(slot, v)));
+        }
+
+        if self.try_reserve_vacant(idx).is_err() {
+            return Err(InsertError::Alloc(v));
+        }
+
+        match unsafe { self.insert_vacant(idx, v) } {
+            Ok(_) => Ok(None),
+            Err(v) => Err(InsertError::Capacity(v)),
+        }
+    }
+
+    fn occupied(&self) -> impl Iterator<Item = (Index, &T)> + '_ {
+        self.redirects
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, group)| {
+                group.iter().enumerate().filter_map(move |(j, &internal)| {
+                    if internal == I::EMPTY {
+                        return None;
+                    }
+
+                    let data = self.data.get(internal.to_usize())?;
+                    Some(((i * SPARSE_RATIO + j) as Index, data))
+                })
+            })
+    }
+
+    fn remove_next(&mut self, next_idx: &mut usize) -> Option<(Index, T)> {
+        let end = self.index_end();
+
+        while *next_idx < end {
+            let idx = *next_idx;
+            *next_idx += 1;
+
+            if self.lookup(idx).is_some() {
+                let v = unsafe { self.c_remove_deferred(idx)? };
+                return Some((idx as Index, v));
+            }
+        }
+
+        None
+    }
+
+    /// Moves the components at or above internal slot `bound` below it and
+    /// drops the free slots there. `bound` must be at least `self.len`.
+    fn pack_below(&mut self, bound: usize) {
+        self.free_slots.retain(|slot| slot.to_usize() < bound);
+
+        for redirect in self.redirects.iter_mut().flatten() {
+            if *redirect == I::EMPTY || redirect.to_usize() < bound {
+                continue;
+            }
+
+            let target = self
+                .free_slots
+                .pop()
+                .expect("pack bound below storage length");
+            if let Some(v) = self.data.take(redirect.to_usize()) {
+                self.data.insert(target.to_usize(), v);
+            }
+
+            *redirect = target;
+        }
+    }
+
+    /// Iterates the redirects of the indices in `range` that are covered by
+    /// a redirect group, skipping the rest of the table.
+    fn redirects_in(&self, range: ops::Range<Index>) -> impl Iterator<Item = (Index, I)> + '_ {
+        let end = (range.end as usize).min(self.index_end());
+        let start = (range.start as usize).min(end);
+
+        (start..end).map(move |idx| {
+            (
+                idx as Index,
+                self.redirects[idx / SPARSE_RATIO][idx % SPARSE_RATIO],
+            )
+        })
+    }
+
+    /// Returns the highest occupied index. Trailing empty groups are mostly
+    /// trimmed, so this rarely scans more than the last group.
+    fn highest_index(&self) -> Option<usize> {
+        self.redirects
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, group)| {
+                let j = group.iter().rposition(|&r| r != I::EMPTY)?;
+                Some(i * SPARSE_RATIO + j)
+            })
+    }
+
     #[inline]
-    pub fn len(&self) -> usize {
-        self.len
+    fn lookup(&self, idx: usize) -> Option<usize> {
+        redirect::lookup(self.redirects.as_flattened(), idx)
     }
 
     #[inline]
-    unsafe fn resolve_to_internal(&self, idx: usize) -> u16 {
+    unsafe fn resolve_to_internal(&self, idx: usize) -> I {
         let group_idx = idx / SPARSE_RATIO;
         let group_sub = idx % SPARSE_RATIO;
+        debug_assert!(
+            group_idx < self.redirects.len(),
+            "index {} is out of range of the storage",
+            idx
+        );
         *self
-            .inner
-            .get_unchecked(group_idx)
             .redirects
+            .get_unchecked(group_idx)
             .get_unchecked(group_sub)
     }
 
+    /// Returns one past the highest index covered by a redirect group, which
+    /// cannot overflow since a `Vec` caps the groups at `isize::MAX` bytes.
+    #[inline]
+    pub(crate) fn index_end(&self) -> usize {
+        self.redirects.len() * SPARSE_RATIO
+    }
+
+    #[inline]
+    fn check_prefill(&mut self, idx: usize) {
+        let required = idx / SPARSE_RATIO + 1;
+
+        if required > self.redirects.len() {
+            self.redirects.resize(required, [I::EMPTY; SPARSE_RATIO]);
+        }
+    }
+
+    /// Reserves everything an insert at the vacant `idx` would grow, so that
+    /// the insert itself does not allocate.
+    fn try_reserve_vacant(&mut self, idx: usize) -> Result<(), TryReserveError> {
+        let required = idx / SPARSE_RATIO + 1;
+
+        if required > self.redirects.len() {
+            self.redirects
+                .try_reserve(required - self.redirects.len())?;
+        }
+
+        if self.free_slots.is_empty() && self.data.len() < I::MAX_SLOTS {
+            // Mirrors the growth step of `find_free`.
+            let step = self.growth_step();
+            let spare = self.data.capacity() - self.data.len();
+            let amount = if spare > 0 { spare.min(step) } else { step };
+            let amount = amount.min(I::MAX_SLOTS - self.data.len());
+
+            self.data.try_reserve(amount)?;
+            self.try_reserve_free(amount)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "smallvec"))]
+    fn try_reserve_free(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.free_slots.try_reserve(additional)
+    }
+
+    #[cfg(feature = "smallvec")]
+    fn try_reserve_free(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        // `SmallVec` has its own error type and std's cannot be built
+        // directly, so any failure is reported as a capacity overflow.
+        self.free_slots
+            .try_reserve(additional)
+            .map_err(|_| Vec::<I>::new().try_reserve(usize::MAX).unwrap_err())
+    }
+
     #[inline]
-    unsafe fn check_prefill(&mut self, idx_cap: usize) {
-        let additional = (idx_cap / SPARSE_RATIO).saturating_sub(self.inner.len());
-        self.inner.reserve(additional);
-        while self.inner.len() / SPARSE_RATIO < idx_cap {
-            self.inner.push(InterleavedGroup::blank());
+    fn expand(&mut self, amount: usize) {
+        let start = self.data.len();
+        let end = (start + amount).min(I::MAX_SLOTS);
+        self.data.reserve(end - start);
+        self.free_slots.reserve(end - start);
+
+        for _ in start..end {
+            self.data.push_vacant();
+        }
+
+        count_op!(self, grows);
+
+        // Hand out the lowest new slot first, the trailing ones then stay
+        // vacant the longest and can be trimmed again.
+        self.free_slots
+            .extend((start..end).rev().map(I::from_usize));
+
+        // Last, so a panicking observer finds the storage consistent.
+        if let Some(f) = &mut self.on_grow {
+            f(end);
         }
     }
 
+    /// Number of data slots to add once the free slots run out.
     #[inline]
-    unsafe fn find_free(&mut self) -> usize {
-        let roundtrip_steps = self.inner.len();
-        let mut i = self.next_free_slot;
+    fn growth_step(&self) -> usize {
+        (self.data.len() / 2).max(self.min_growth)
+    }
+
+    #[inline]
+    fn find_free(&mut self) -> Option<usize> {
+        loop {
+            if let Some(slot) = self.free_slots.pop() {
+                // Free slots are vacant, handing out an occupied one would
+                // alias two indices.
+                debug_assert!(
+                    self.data.get(slot.to_usize()).is_none(),
+                    "free slot {} is occupied",
+                    slot.to_usize()
+                );
+                return Some(slot.to_usize());
+            }
 
-        for _ in 0..roundtrip_steps {
-            if i == self.inner.len() {
-                i = 0;
+            if self.data.len() >= I::MAX_SLOTS {
+                return None;
             }
 
-            let e = self.inner.get_unchecked(i);
-            if e.data.is_none() {
-                self.next_free_slot = i;
-                return i;
+            // Grow geometrically so large fills only reallocate a logarithmic
+            // number of times, but use up spare capacity first.
+            let step = self.growth_step();
+            let spare = self.data.capacity() - self.data.len();
+
+            if spare > 0 {
+                self.expand(spare.min(step));
+            } else {
+                self.expand(step);
             }
+        }
+    }
 
-            i += 1;
+    #[inline]
+    unsafe fn c_insert(&mut self, idx: usize, v: T) -> Result<(), T> {
+        if let Some(existing) = self.lookup(idx) {
+            count_op!(self, overwrites);
+            drop(mem::replace(self.data.get_unchecked_mut(existing), v));
+            return Ok(());
         }
 
-        self.inner.push(InterleavedGroup::blank());
-        self.next_free_slot = self.inner.len() - 1;
-        self.next_free_slot
+        self.insert_vacant(idx, v).map(drop)
     }
 
+    /// Stores `v` at the unoccupied index `idx` and returns the internal slot used.
     #[inline]
-    unsafe fn c_insert(&mut self, idx: usize, v: T) {
+    unsafe fn insert_vacant(&mut self, idx: usize, v: T) -> Result<usize, T> {
         self.check_prefill(idx);
-        let group_idx = idx / SPARSE_RATIO;
-        let group_sub = idx % SPARSE_RATIO;
-        let internal_point = self.find_free();
-        *self
-            .inner
-            .get_unchecked_mut(group_idx)
-            .redirects
-            .get_unchecked_mut(group_sub) = internal_point as u16;
-        self.inner.get_unchecked_mut(internal_point).data = Some(v);
+
+        let internal_point = match self.find_free() {
+            Some(internal_point) => internal_point,
+            None => return Err(v),
+        };
+
+        redirect::occupy(self.redirects.as_flattened_mut(), idx, internal_point);
+        self.data.insert(internal_point, v);
         self.len += 1;
+        count_op!(self, inserts);
+        Ok(internal_point)
     }
 
     #[inline]
     unsafe fn c_get(&self, idx: usize) -> Option<&T> {
         let internal = self.resolve_to_internal(idx);
-        self.inner.get_unchecked(internal as usize).data.as_ref()
+
+        if internal == I::EMPTY {
+            return None;
+        }
+
+        Some(self.data.get_unchecked(internal.to_usize()))
     }
 
     #[inline]
     unsafe fn c_get_mut(&mut self, idx: usize) -> Option<&mut T> {
         let internal = self.resolve_to_internal(idx);
-        self.inner
-            .get_unchecked_mut(internal as usize)
-            .data
-            .as_mut()
+
+        if internal == I::EMPTY {
+            return None;
+        }
+
+        Some(self.data.get_unchecked_mut(internal.to_usize()))
     }
 
     #[inline]
     unsafe fn c_remove(&mut self, idx: usize) -> Option<T> {
-        let internal = self.resolve_to_internal(idx);
+        self.c_remove_inner(idx, false)
+    }
+
+    /// Removes like `c_remove`, but under `LowestFirst` leaves sorting and
+    /// trimming to `finish_removals`.
+    #[inline]
+    unsafe fn c_remove_deferred(&mut self, idx: usize) -> Option<T> {
+        self.c_remove_inner(idx, self.policy == SlotPolicy::LowestFirst)
+    }
+
+    /// Sorts the free list and trims the tail after a run of
+    /// `c_remove_deferred`.
+    fn finish_removals(&mut self) {
+        if self.policy == SlotPolicy::LowestFirst {
+            self.sort_free_slots();
+            self.trim_tail();
+        }
+    }
+
+    /// Restores the descending free list order `LowestFirst` pops from.
+    fn sort_free_slots(&mut self) {
+        if self.policy == SlotPolicy::LowestFirst {
+            self.free_slots
+                .sort_unstable_by_key(|slot| Reverse(slot.to_usize()));
+        }
+    }
+
+    #[inline]
+    unsafe fn c_remove_inner(&mut self, idx: usize, defer: bool) -> Option<T> {
+        // Trailing groups get trimmed on removal, so a repeated removal may
+        // point past the last one.
+        let internal = redirect::vacate(self.redirects.as_flattened_mut(), idx)?;
+        let group_idx = idx / SPARSE_RATIO;
+
+        #[cfg(feature = "generations")]
+        self.bump_generation(idx);
         self.len -= 1;
-        self.inner.get_unchecked_mut(internal as usize).data.take()
+        count_op!(self, removes);
+        let v = self.data.take(internal.to_usize());
+
+        if defer {
+            self.free_slots.push(internal);
+        } else {
+            self.release_slot(internal);
+
+            if group_idx + 1 == self.redirects.len() || internal.to_usize() + 1 == self.data.len() {
+                self.trim_tail();
+            }
+        }
+
+        #[cfg(feature = "log")]
+        self.check_fragmentation();
+
+        v
+    }
+
+    #[cfg(feature = "log")]
+    #[inline]
+    fn check_fragmentation(&mut self) {
+        let mut warning = match self.fragmentation_warning {
+            Some(warning) => warning,
+            None => return,
+        };
+
+        warning.countdown -= 1;
+
+        if warning.countdown == 0 {
+            warning.countdown = FRAGMENTATION_CHECK_INTERVAL;
+
+            // Every slot up to the highest occupied one is allocated, so this
+            // never underestimates and the exact scan can be skipped below it.
+            let estimate = 1.0 - self.len as f32 / self.data.len().max(1) as f32;
+            let fragmentation = if estimate > warning.limit {
+                self.fragmentation()
+            } else {
+                estimate
+            };
+
+            if fragmentation <= warning.limit {
+                warning.armed = true;
+            } else if warning.armed {
+                warning.armed = false;
+                log::warn!(
+                    "storage fragmentation {:.2} exceeds {:.2} with {} components in {} slots, \
+                     consider compacting",
+                    fragmentation,
+                    warning.limit,
+                    self.len,
+                    self.data.len()
+                );
+            }
+        }
+
+        self.fragmentation_warning = Some(warning);
+    }
+
+    #[cfg(feature = "generations")]
+    #[inline]
+    fn generation_of(&self, idx: usize) -> u32 {
+        self.generations.get(idx).copied().unwrap_or(0)
+    }
+
+    /// Counts a removal from `idx`. Generations of trimmed groups are kept, so
+    /// stale handles stay stale.
+    #[cfg(feature = "generations")]
+    fn bump_generation(&mut self, idx: usize) {
+        if idx >= self.generations.len() {
+            self.generations.resize(idx + 1, 0);
+        }
+
+        self.generations[idx] = self.generations[idx].wrapping_add(1);
+    }
+
+    /// Pops trailing empty redirect groups and trailing free data slots,
+    /// keeping the memory allocated.
+    fn trim_tail(&mut self) {
+        while let Some(group) = self.redirects.last() {
+            if group.iter().any(|&redirect| redirect != I::EMPTY) {
+                break;
+            }
+
+            self.redirects.pop();
+        }
+
+        match self.policy {
+            SlotPolicy::Lifo => {
+                while let Some(&slot) = self.free_slots.last() {
+                    if slot.to_usize() + 1 != self.data.len() {
+                        break;
+                    }
+
+                    self.free_slots.pop();
+                    self.data.truncate(slot.to_usize());
+                }
+            }
+            SlotPolicy::LowestFirst => {
+                // The highest slots sit at the front of the sorted list.
+                let trailing = self
+                    .free_slots
+                    .iter()
+                    .zip((0..self.data.len()).rev())
+                    .take_while(|&(slot, tail)| slot.to_usize() == tail)
+                    .count();
+
+                self.free_slots.drain(..trailing);
+                self.data.truncate(self.data.len() - trailing);
+            }
+        }
+    }
+
+    /// Puts a vacated slot back on the free list according to the policy.
+    #[inline]
+    fn release_slot(&mut self, slot: I) {
+        match self.policy {
+            SlotPolicy::Lifo => self.free_slots.push(slot),
+            SlotPolicy::LowestFirst => {
+                // Kept in descending order, so popping yields the lowest slot.
+                let at = self
+                    .free_slots
+                    .partition_point(|free| free.to_usize() > slot.to_usize());
+                self.free_slots.insert(at, slot);
+            }
+        }
     }
 
     #[inline]
@@ -126,24 +2094,91 @@ impl<T> IdvStorage<T> {
     where
         B: BitSetLike,
     {
-        let mut garbage = Vec::new();
+        count_op!(self, cleans);
+        self.remove_mask(&has);
+    }
+}
 
-        for (i, e) in self.inner.iter_mut().enumerate() {
-            for j in 0..SPARSE_RATIO {
-                if has.contains((i * j) as u32) {
-                    let real = e.redirects[j];
-                    garbage.push(real);
-                }
-            }
-        }
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> ops::Index<Index>
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    type Output = T;
+
+    fn index(&self, idx: Index) -> &T {
+        self.get_checked(idx)
+            .unwrap_or_else(|| panic!("no component at index {}", idx))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> ops::IndexMut<Index>
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn index_mut(&mut self, idx: Index) -> &mut T {
+        self.get_checked_mut(idx)
+            .unwrap_or_else(|| panic!("no component at index {}", idx))
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone> Extend<(Index, T)>
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn extend<It: IntoIterator<Item = (Index, T)>>(&mut self, iter: It) {
+        self.insert_many(iter);
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone + Default>
+    FromIterator<(Index, T)> for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    fn from_iter<It: IntoIterator<Item = (Index, T)>>(iter: It) -> Self {
+        let mut storage = IdvStorage::default();
+        storage.insert_many(iter);
+        storage
+    }
+}
+
+struct Components<'a, T, const SPARSE_RATIO: usize, I, A: Allocator + Clone>(
+    &'a IdvStorage<T, SPARSE_RATIO, I, A>,
+);
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> fmt::Debug
+    for Components<'_, T, SPARSE_RATIO, I, A>
+where
+    T: fmt::Debug,
+    I: RedirectIndex,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.occupied()).finish()
+    }
+}
 
-        for idx in garbage {
-            self.inner[idx as usize].data = None;
+/// Prints a summary of the storage occupancy. The alternate form `{:#?}`
+/// additionally lists every occupied logical index with its value.
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> fmt::Debug
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+where
+    T: fmt::Debug,
+    I: RedirectIndex,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alternate = f.alternate();
+        let mut s = f.debug_struct("IdvStorage");
+        s.field("len", &self.len)
+            .field("groups", &self.redirects.len())
+            .field("slots", &self.data.len())
+            .field("free_slots", &self.free_slots.len());
+
+        if alternate {
+            s.field("components", &Components(self));
         }
+
+        s.finish()
     }
 }
 
-impl<T> UnprotectedStorage<T> for IdvStorage<T> {
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone + Default>
+    UnprotectedStorage<T> for IdvStorage<T, SPARSE_RATIO, I, A>
+{
     #[inline]
     unsafe fn clean<B>(&mut self, has: B)
     where
@@ -154,23 +2189,49 @@ impl<T> UnprotectedStorage<T> for IdvStorage<T> {
 
     #[inline]
     unsafe fn get(&self, idx: Index) -> &T {
-        self.c_get(idx as usize).unwrap()
+        if cfg!(feature = "checked") {
+            // A redirect always points to an occupied slot, only the caller's
+            // side of the contract needs checking.
+            let internal = self
+                .lookup(idx as usize)
+                .unwrap_or_else(|| missing_component(idx));
+            return self.data.get_unchecked(internal);
+        }
+
+        self.c_get(idx as usize)
+            .unwrap_or_else(|| missing_component(idx))
     }
 
     #[inline]
     unsafe fn get_mut(&mut self, idx: Index) -> &mut T {
-        self.c_get_mut(idx as usize).unwrap()
+        if cfg!(feature = "checked") {
+            let internal = self
+                .lookup(idx as usize)
+                .unwrap_or_else(|| missing_component(idx));
+            return self.data.get_unchecked_mut(internal);
+        }
+
+        self.c_get_mut(idx as usize)
+            .unwrap_or_else(|| missing_component(idx))
     }
 
     #[inline]
     unsafe fn insert(&mut self, idx: Index, v: T) {
-        self.c_insert(idx as usize, v);
+        if self.c_insert(idx as usize, v).is_err() {
+            panic!("storage cannot hold more than {} components", I::MAX_SLOTS);
+        }
     }
 
     #[inline]
     unsafe fn remove(&mut self, idx: Index) -> T {
-        self.c_remove(idx as usize).unwrap()
+        self.c_remove(idx as usize)
+            .unwrap_or_else(|| missing_removal(idx))
     }
 }
 
-unsafe impl<T> DistinctStorage for IdvStorage<T> {}
+// Distinct indices never share an internal slot, which also makes the storage
+// eligible for `ParJoin` when the `parallel` feature is enabled.
+unsafe impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> DistinctStorage
+    for IdvStorage<T, SPARSE_RATIO, I, A>
+{
+}
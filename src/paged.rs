@@ -0,0 +1,180 @@
+use crate::{missing_component, missing_removal, IdvStorage, MemoryStats, RedirectIndex};
+use alloc::collections::BTreeMap;
+use core::mem;
+use specs::hibitset::BitSetLike;
+use specs::storage::{DistinctStorage, UnprotectedStorage};
+use specs::world::Index;
+
+/// Number of consecutive indices covered by each page of a
+/// [`PagedIdvStorage`].
+pub const PAGE_LEN: usize = 256;
+
+/// An interleaved storage split into pages of [`PAGE_LEN`] indices, which
+/// only allocates the pages in use.
+pub struct PagedIdvStorage<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    pages: BTreeMap<Index, IdvStorage<T, SPARSE_RATIO, I>>,
+    len: usize,
+}
+
+impl<T, const SPARSE_RATIO: usize, I> Default for PagedIdvStorage<T, SPARSE_RATIO, I> {
+    fn default() -> Self {
+        PagedIdvStorage {
+            pages: BTreeMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone, const SPARSE_RATIO: usize, I: Clone> Clone for PagedIdvStorage<T, SPARSE_RATIO, I> {
+    fn clone(&self) -> Self {
+        PagedIdvStorage {
+            pages: self.pages.clone(),
+            len: self.len,
+        }
+    }
+}
+
+#[inline]
+fn split(idx: Index) -> (Index, Index) {
+    let page_len = PAGE_LEN as Index;
+    (idx / page_len, idx % page_len)
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> PagedIdvStorage<T, SPARSE_RATIO, I> {
+    /// Returns the number of components stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of pages allocated, each covering [`PAGE_LEN`]
+    /// indices with at least one component.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.get_checked(idx).is_some()
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        let (page, local) = split(idx);
+        self.pages.get(&page)?.get_checked(local)
+    }
+
+    /// Returns a mutable reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked_mut(&mut self, idx: Index) -> Option<&mut T> {
+        let (page, local) = split(idx);
+        self.pages.get_mut(&page)?.get_checked_mut(local)
+    }
+
+    /// Stores `v` at `idx` and returns the component previously stored there.
+    pub fn replace(&mut self, idx: Index, v: T) -> Option<T> {
+        let (page, local) = split(idx);
+        let old = self.pages.entry(page).or_default().replace(local, v);
+
+        if old.is_none() {
+            self.len += 1;
+        }
+
+        old
+    }
+
+    /// Removes and returns the component at `idx`, if any. Drops the page of
+    /// `idx` if this was its last component.
+    pub fn take(&mut self, idx: Index) -> Option<T> {
+        let (page, local) = split(idx);
+        let storage = self.pages.get_mut(&page)?;
+        let v = storage.take(local)?;
+
+        if storage.is_empty() {
+            self.pages.remove(&page);
+        }
+
+        self.len -= 1;
+        Some(v)
+    }
+
+    /// Drops every component along with all pages.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.pages.clear();
+    }
+
+    /// Reports the memory of the pages, counting one map entry per page as
+    /// redirect memory.
+    pub fn memory_usage(&self) -> MemoryStats {
+        let entry_bytes = mem::size_of::<(Index, IdvStorage<T, SPARSE_RATIO, I>)>();
+        let mut stats = MemoryStats {
+            redirect_bytes: self.pages.len() * entry_bytes,
+            data_bytes: 0,
+            free_list_bytes: 0,
+            occupancy_bytes: 0,
+            occupied_slots: 0,
+            free_slots: 0,
+        };
+
+        for page in self.pages.values() {
+            let usage = page.memory_usage();
+            stats.redirect_bytes += usage.redirect_bytes;
+            stats.data_bytes += usage.data_bytes;
+            stats.free_list_bytes += usage.free_list_bytes;
+            stats.occupancy_bytes += usage.occupancy_bytes;
+            stats.occupied_slots += usage.occupied_slots;
+            stats.free_slots += usage.free_slots;
+        }
+
+        stats
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> UnprotectedStorage<T>
+    for PagedIdvStorage<T, SPARSE_RATIO, I>
+{
+    unsafe fn clean<B>(&mut self, has: B)
+    where
+        B: BitSetLike,
+    {
+        for idx in has.iter() {
+            self.take(idx);
+        }
+    }
+
+    #[inline]
+    unsafe fn get(&self, idx: Index) -> &T {
+        self.get_checked(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    #[inline]
+    unsafe fn get_mut(&mut self, idx: Index) -> &mut T {
+        self.get_checked_mut(idx)
+            .unwrap_or_else(|| missing_component(idx))
+    }
+
+    unsafe fn insert(&mut self, idx: Index, v: T) {
+        self.replace(idx, v);
+    }
+
+    unsafe fn remove(&mut self, idx: Index) -> T {
+        self.take(idx).unwrap_or_else(|| missing_removal(idx))
+    }
+}
+
+// Each index lives in exactly one page, whose storage keeps distinct indices
+// in distinct slots.
+unsafe impl<T, const SPARSE_RATIO: usize, I> DistinctStorage
+    for PagedIdvStorage<T, SPARSE_RATIO, I>
+{
+}
@@ -0,0 +1,133 @@
+use crate::slots::Slot;
+use crate::{Allocator, IdvStorage, RedirectIndex};
+use core::marker::PhantomData;
+use specs::hibitset::{BitProducer, BitSetLike};
+use specs::rayon::iter::plumbing::{
+    bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer,
+};
+use specs::rayon::iter::ParallelIterator;
+use specs::world::Index;
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Mutably iterates the components at the indices set in `mask` on the
+    /// rayon thread pool.
+    pub fn par_iter_mut<'a, B>(&'a mut self, mask: &'a B) -> ParIterMut<'a, B, T, SPARSE_RATIO, I>
+    where
+        B: BitSetLike + Send + Sync,
+    {
+        ParIterMut {
+            mask,
+            redirects: &self.redirects,
+            data: self.data.as_mut_ptr(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Parallel iterator returned by [`IdvStorage::par_iter_mut`], splitting the
+/// mask into disjoint index sets.
+pub struct ParIterMut<'a, B, T, const SPARSE_RATIO: usize, I> {
+    mask: &'a B,
+    redirects: &'a [[I; SPARSE_RATIO]],
+    data: *mut Slot<T>,
+    marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: the iterator hands out `&mut T` to the threads it runs on, and
+// every component is handed out at most once.
+unsafe impl<B: Sync, T: Send, const SPARSE_RATIO: usize, I: Sync> Send
+    for ParIterMut<'_, B, T, SPARSE_RATIO, I>
+{
+}
+
+impl<'a, B, T, const SPARSE_RATIO: usize, I> ParallelIterator
+    for ParIterMut<'a, B, T, SPARSE_RATIO, I>
+where
+    B: BitSetLike + Send + Sync,
+    T: Send,
+    I: RedirectIndex + Sync,
+{
+    type Item = (Index, &'a mut T);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let producer = Producer {
+            bits: BitProducer(self.mask.iter(), 3),
+            redirects: self.redirects,
+            data: self.data,
+            marker: PhantomData,
+        };
+
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+struct Producer<'a, B: Send + Sync, T, const SPARSE_RATIO: usize, I> {
+    bits: BitProducer<'a, B>,
+    redirects: &'a [[I; SPARSE_RATIO]],
+    data: *mut Slot<T>,
+    marker: PhantomData<&'a mut T>,
+}
+
+// SAFETY: see `ParIterMut`, producers cover disjoint sets of indices.
+unsafe impl<B: Send + Sync, T: Send, const SPARSE_RATIO: usize, I: Sync> Send
+    for Producer<'_, B, T, SPARSE_RATIO, I>
+{
+}
+
+impl<'a, B, T, const SPARSE_RATIO: usize, I> UnindexedProducer
+    for Producer<'a, B, T, SPARSE_RATIO, I>
+where
+    B: BitSetLike + Send + Sync,
+    T: Send,
+    I: RedirectIndex + Sync,
+{
+    type Item = (Index, &'a mut T);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let (bits, other) = self.bits.split();
+        let (redirects, data) = (self.redirects, self.data);
+        let other = other.map(|bits| Producer {
+            bits,
+            redirects,
+            data,
+            marker: PhantomData,
+        });
+
+        (
+            Producer {
+                bits,
+                redirects,
+                data,
+                marker: PhantomData,
+            },
+            other,
+        )
+    }
+
+    fn fold_with<F>(self, folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        let (redirects, data) = (self.redirects, self.data);
+
+        folder.consume_iter(self.bits.0.filter_map(move |idx| {
+            let internal = redirects.get(idx as usize / SPARSE_RATIO)?[idx as usize % SPARSE_RATIO];
+
+            if internal == I::EMPTY {
+                return None;
+            }
+
+            // SAFETY: a redirected slot always holds a value. Split producers
+            // yield disjoint indices and distinct indices never share an
+            // internal slot, so no two references alias.
+            Some((idx, unsafe {
+                &mut *(*data.add(internal.to_usize())).0.as_mut_ptr()
+            }))
+        }))
+    }
+}
@@ -0,0 +1,37 @@
+use crate::RedirectIndex;
+
+// The redirect bookkeeping shared by `IdvStorage` and `FixedIdvStorage`.
+// Both map every index to an internal data slot through a flat run of
+// redirects, `I::EMPTY` marking a vacant index; they only differ in where the
+// redirects live and how free slots are found.
+
+/// Returns the internal slot `idx` redirects to, if it holds a component.
+#[inline]
+pub(crate) fn lookup<I: RedirectIndex>(redirects: &[I], idx: usize) -> Option<usize> {
+    match redirects.get(idx) {
+        Some(&internal) if internal != I::EMPTY => Some(internal.to_usize()),
+        _ => None,
+    }
+}
+
+/// Points the vacant `idx` at the internal slot `slot`. Panics if `idx` is
+/// past the redirects.
+#[inline]
+pub(crate) fn occupy<I: RedirectIndex>(redirects: &mut [I], idx: usize, slot: usize) {
+    debug_assert!(redirects[idx] == I::EMPTY, "index {} is occupied", idx);
+    debug_assert!(slot < I::MAX_SLOTS);
+    redirects[idx] = I::from_usize(slot);
+}
+
+/// Marks `idx` vacant and returns the internal slot it redirected to, which
+/// the caller has to free. Returns `None` if `idx` held no component.
+#[inline]
+pub(crate) fn vacate<I: RedirectIndex>(redirects: &mut [I], idx: usize) -> Option<I> {
+    let redirect = redirects.get_mut(idx)?;
+
+    if *redirect == I::EMPTY {
+        return None;
+    }
+
+    Some(core::mem::replace(redirect, I::EMPTY))
+}
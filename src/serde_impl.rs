@@ -0,0 +1,178 @@
+use crate::{Allocator, IdvError, IdvStorage, RedirectIndex, MASK_INDEX_LIMIT};
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use specs::world::Index;
+
+/// Serializes the storage as `(index, value)` pairs in ascending index
+/// order, independent of the internal layout.
+impl<T, const SPARSE_RATIO: usize, I, A> Serialize for IdvStorage<T, SPARSE_RATIO, I, A>
+where
+    T: Serialize,
+    I: RedirectIndex,
+    A: Allocator + Clone,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.occupied())
+    }
+}
+
+struct StorageVisitor<T, const SPARSE_RATIO: usize, I> {
+    max_index: usize,
+    /// Whether the pairs hold the gap to the previous index instead of the
+    /// index itself.
+    compact: bool,
+    marker: PhantomData<(T, I)>,
+}
+
+impl<'de, T, const SPARSE_RATIO: usize, I> Visitor<'de> for StorageVisitor<T, SPARSE_RATIO, I>
+where
+    T: Deserialize<'de>,
+    I: RedirectIndex,
+{
+    type Value = IdvStorage<T, SPARSE_RATIO, I>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.compact {
+            f.write_str("a sequence of (gap, value) pairs")
+        } else {
+            f.write_str("a sequence of (index, value) pairs")
+        }
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut storage = IdvStorage::default();
+
+        // Cap the hint so a malicious length prefix cannot force a huge
+        // allocation before any element has been read.
+        let hint = seq.size_hint().unwrap_or(0).min(4096);
+        storage.try_reserve(hint).map_err(A::Error::custom)?;
+
+        // One past the previous index, where the gaps of the compact form
+        // count from.
+        let mut next = 0u64;
+
+        while let Some((n, v)) = seq.next_element::<(Index, T)>()? {
+            let idx = if self.compact {
+                let idx = next + u64::from(n);
+
+                if idx > u64::from(Index::MAX) {
+                    return Err(A::Error::custom(format_args!(
+                        "gap {} after index {} overflows the index range",
+                        n,
+                        next - 1
+                    )));
+                }
+
+                idx as Index
+            } else {
+                n
+            };
+
+            // The redirect table grows with the highest index, so an
+            // unbounded index would let the input pick the allocation size.
+            if idx as usize >= self.max_index {
+                return Err(A::Error::custom(format_args!(
+                    "{}, the limit is {}",
+                    IdvError::OutOfRange(idx),
+                    self.max_index
+                )));
+            }
+
+            match storage.checked_insert(idx, v) {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    return Err(A::Error::custom(format_args!(
+                        "index {} appears more than once",
+                        idx
+                    )))
+                }
+                Err(err) => return Err(A::Error::custom(IdvError::from(err))),
+            }
+
+            next = u64::from(idx) + 1;
+        }
+
+        // The insert path cannot build an inconsistent layout, but debug and
+        // `checked` builds confirm it before the storage escapes.
+        if cfg!(any(debug_assertions, feature = "checked")) {
+            storage
+                .audit()
+                .map_err(|err| A::Error::custom(IdvError::from(err)))?;
+        }
+
+        Ok(storage)
+    }
+}
+
+/// Rebuilds the storage from `(index, value)` pairs, rejecting repeated
+/// indices and those at or above [`MASK_INDEX_LIMIT`].
+impl<'de, T, const SPARSE_RATIO: usize, I> Deserialize<'de> for IdvStorage<T, SPARSE_RATIO, I>
+where
+    T: Deserialize<'de>,
+    I: RedirectIndex,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        IdvStorage::deserialize_bounded(deserializer, MASK_INDEX_LIMIT)
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorage<T, SPARSE_RATIO, I> {
+    /// Deserializes a storage like its `Deserialize` impl, but rejects every
+    /// index at or above `max_index` instead of [`MASK_INDEX_LIMIT`].
+    pub fn deserialize_bounded<'de, D>(deserializer: D, max_index: usize) -> Result<Self, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(StorageVisitor {
+            max_index,
+            compact: false,
+            marker: PhantomData,
+        })
+    }
+
+    /// Rebuilds a storage written by
+    /// [`serialize_compact`](Self::serialize_compact).
+    pub fn deserialize_compact<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(StorageVisitor {
+            max_index: MASK_INDEX_LIMIT,
+            compact: true,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Serializes the storage as `(gap, value)` pairs, where the gap counts the
+    /// vacant indices since the previous component.
+    pub fn serialize_compact<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        let mut next = 0;
+
+        serializer.collect_seq(self.occupied().map(|(idx, v)| {
+            let gap = idx - next;
+            next = idx + 1;
+            (gap, v)
+        }))
+    }
+}
@@ -0,0 +1,295 @@
+use crate::bits::BitVec;
+use crate::{allocator, Allocator};
+use alloc::collections::TryReserveError;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::slice;
+
+/// A single data slot, aligned to its own 64 byte cache line with the
+/// `cache-aligned` feature.
+#[repr(C)]
+#[cfg_attr(feature = "cache-aligned", repr(align(64)))]
+pub(crate) struct Slot<T>(pub(crate) MaybeUninit<T>);
+
+/// Data slots without a per-slot discriminant. Which slots hold an
+/// initialized value is tracked in a separate bit vector.
+pub(crate) struct Slots<T, A: Allocator + Clone> {
+    slots: alloc_vec!(Slot<T>, A),
+    /// Also holds the allocator both buffers come from.
+    occupied: BitVec<A>,
+}
+
+impl<T, A: Allocator + Clone> Slots<T, A> {
+    pub(crate) fn new_in(alloc: A) -> Self {
+        Slots {
+            slots: allocator::new_vec(&alloc),
+            occupied: BitVec::new_in(alloc),
+        }
+    }
+
+    pub(crate) fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Slots {
+            slots: allocator::vec_with_capacity(capacity, &alloc),
+            occupied: BitVec::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn allocator(&self) -> &A {
+        self.occupied.allocator()
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Bytes allocated for the bits marking occupied slots.
+    #[inline]
+    pub(crate) fn occupancy_bytes(&self) -> usize {
+        self.occupied.allocated_bytes()
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.slots.reserve(additional);
+        self.occupied.reserve(self.slots.len() + additional);
+    }
+
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.slots.try_reserve(additional)?;
+        self.occupied.try_reserve(self.slots.len() + additional)
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.slots.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+    }
+
+    /// Appends an empty slot.
+    #[inline]
+    pub(crate) fn push_vacant(&mut self) {
+        self.slots.push(Slot(MaybeUninit::uninit()));
+    }
+
+    /// Drops the slots at and above `len`, along with any values in them.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        for i in len..self.slots.len() {
+            self.take(i);
+        }
+
+        self.slots.truncate(len);
+    }
+
+    /// Drops every value and removes every slot.
+    pub(crate) fn clear(&mut self) {
+        let slots = self.slots.as_mut_ptr();
+
+        // SAFETY: slots need no drop, and dropping the values below only
+        // touches the allocation, which shortening the vector keeps. With the
+        // slots gone before any destructor runs, a panicking one leaves an
+        // empty, consistent set of slots and leaks the remaining values.
+        unsafe {
+            self.slots.set_len(0);
+        }
+
+        self.occupied.clear_with(|i| {
+            // SAFETY: set bits mark initialized slots.
+            unsafe {
+                ptr::drop_in_place((*slots.add(i)).0.as_mut_ptr());
+            }
+        });
+    }
+
+    /// Drops every value while keeping the slots.
+    pub(crate) fn vacate_all(&mut self) {
+        // Each value is unmarked before it is dropped, a panicking destructor
+        // then leaks the remaining values instead of dropping them twice
+        // later on.
+        let slots = &mut self.slots;
+
+        self.occupied.clear_with(|i| {
+            // SAFETY: set bits mark initialized slots.
+            unsafe {
+                ptr::drop_in_place(slots[i].0.as_mut_ptr());
+            }
+        });
+    }
+
+    #[inline]
+    fn is_occupied(&self, i: usize) -> bool {
+        i < self.slots.len() && self.occupied.contains(i)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, i: usize) -> Option<&T> {
+        if self.is_occupied(i) {
+            // SAFETY: the slot is in bounds and initialized.
+            Some(unsafe { self.get_unchecked(i) })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if self.is_occupied(i) {
+            // SAFETY: the slot is in bounds and initialized.
+            Some(unsafe { self.get_unchecked_mut(i) })
+        } else {
+            None
+        }
+    }
+
+    /// Safety: slot `i` must exist and hold a value.
+    #[inline]
+    pub(crate) unsafe fn get_unchecked(&self, i: usize) -> &T {
+        debug_assert!(self.is_occupied(i), "data slot {} is empty", i);
+        &*self.slots.get_unchecked(i).0.as_ptr()
+    }
+
+    /// Safety: slot `i` must exist and hold a value.
+    #[inline]
+    pub(crate) unsafe fn get_unchecked_mut(&mut self, i: usize) -> &mut T {
+        debug_assert!(self.is_occupied(i), "data slot {} is empty", i);
+        &mut *self.slots.get_unchecked_mut(i).0.as_mut_ptr()
+    }
+
+    /// Stores `v` in slot `i`, returning the value previously held there.
+    #[inline]
+    pub(crate) fn insert(&mut self, i: usize, v: T) -> Option<T> {
+        let old = self.take(i);
+        self.slots[i].0 = MaybeUninit::new(v);
+        self.occupied.add(i);
+        old
+    }
+
+    /// Moves the value out of slot `i`, leaving it empty.
+    #[inline]
+    pub(crate) fn take(&mut self, i: usize) -> Option<T> {
+        if i < self.slots.len() && self.occupied.remove(i) {
+            // SAFETY: the bit was set, so the slot was initialized, and it is
+            // no longer marked as such.
+            Some(unsafe { self.slots.get_unchecked(i).0.as_ptr().read() })
+        } else {
+            None
+        }
+    }
+
+    /// Overwrites the memory of the empty slot `i` with zeros. The slot may
+    /// already be truncated away, as long as it is within the capacity.
+    #[cfg(feature = "zeroize")]
+    pub(crate) fn zero_vacant(&mut self, i: usize) {
+        use zeroize::Zeroize;
+
+        assert!(i < self.slots.capacity() && !self.is_occupied(i));
+        // SAFETY: the slot lies within the allocation and holds no value, so
+        // writing over its `MaybeUninit` is fine.
+        unsafe { (*self.slots.as_mut_ptr().add(i)).0.zeroize() }
+    }
+
+    /// Returns the values of the occupied slots `0..len` as a slice, or `None`
+    /// if alignment padding makes slots larger than values.
+    pub(crate) unsafe fn prefix_slice(&self, len: usize) -> Option<&[T]> {
+        if mem::size_of::<Slot<T>>() != mem::size_of::<T>() {
+            return None;
+        }
+
+        debug_assert!((0..len).all(|i| self.is_occupied(i)));
+        // Slots are `repr(C)` around the value, so with equal sizes they are
+        // laid out exactly like an array of values.
+        Some(slice::from_raw_parts(self.slots.as_ptr() as *const T, len))
+    }
+
+    /// Iterates the values of all occupied slots in slot order.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        let occupied = &self.occupied;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| occupied.contains(i))
+            // SAFETY: set bits mark initialized slots.
+            .map(|(_, slot)| unsafe { &*slot.0.as_ptr() })
+    }
+
+    /// Mutably iterates the values of all occupied slots in slot order.
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    /// Mutably iterates all occupied slots along with their position.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        let occupied = &self.occupied;
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter(move |&(i, _)| occupied.contains(i))
+            // SAFETY: set bits mark initialized slots.
+            .map(|(i, slot)| (i, unsafe { &mut *slot.0.as_mut_ptr() }))
+    }
+
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *const Slot<T> {
+        self.slots.as_ptr()
+    }
+
+    #[inline]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut Slot<T> {
+        self.slots.as_mut_ptr()
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for Slots<T, A> {
+    fn clone(&self) -> Self {
+        let mut slots = Slots::with_capacity_in(self.slots.len(), self.allocator().clone());
+
+        for _ in 0..self.slots.len() {
+            slots.push_vacant();
+        }
+
+        for i in self.occupied.iter() {
+            // SAFETY: set bits mark initialized slots.
+            let v = unsafe { self.get_unchecked(i) };
+            slots.insert(i, v.clone());
+        }
+
+        slots
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.truncate(source.len());
+
+        for i in 0..self.len() {
+            match source.get(i) {
+                // SAFETY: the slot was just checked to be occupied.
+                Some(v) if self.is_occupied(i) => {
+                    unsafe { self.get_unchecked_mut(i) }.clone_from(v)
+                }
+                Some(v) => {
+                    self.insert(i, v.clone());
+                }
+                None => {
+                    self.take(i);
+                }
+            }
+        }
+
+        for i in self.len()..source.len() {
+            self.push_vacant();
+
+            if let Some(v) = source.get(i) {
+                self.insert(i, v.clone());
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> Drop for Slots<T, A> {
+    fn drop(&mut self) {
+        self.vacate_all();
+    }
+}
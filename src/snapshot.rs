@@ -0,0 +1,44 @@
+use crate::{IdvStorage, RedirectIndex};
+use specs::world::Index;
+
+/// An immutable copy of an [`IdvStorage`], taken with [`IdvStorage::snapshot`]
+/// and applied any number of times with [`IdvStorage::restore`].
+#[derive(Clone)]
+pub struct Snapshot<T, const SPARSE_RATIO: usize = 4, I = u16> {
+    storage: IdvStorage<T, SPARSE_RATIO, I>,
+}
+
+impl<T: Clone, const SPARSE_RATIO: usize, I: RedirectIndex> IdvStorage<T, SPARSE_RATIO, I> {
+    /// Captures the current contents of the storage.
+    pub fn snapshot(&self) -> Snapshot<T, SPARSE_RATIO, I> {
+        Snapshot {
+            storage: self.clone(),
+        }
+    }
+
+    /// Resets the storage to the contents of `snapshot`, reusing the memory
+    /// already allocated and cloning components in place where possible.
+    pub fn restore(&mut self, snapshot: &Snapshot<T, SPARSE_RATIO, I>) {
+        self.clone_from(&snapshot.storage);
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex> Snapshot<T, SPARSE_RATIO, I> {
+    /// Returns the number of components captured.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns `true` if no components were captured.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    /// Returns a reference to the captured component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&T> {
+        self.storage.get_checked(idx)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{Allocator, Global, IdvStorage, Iter, RedirectIndex};
+use specs::hibitset::BitSetLike;
+use specs::world::Index;
+
+/// A copyable, read-only handle to an [`IdvStorage`], returned by
+/// [`IdvStorage::as_read`].
+pub struct ReadView<'a, T, const SPARSE_RATIO: usize = 4, I = u16, A: Allocator + Clone = Global> {
+    storage: &'a IdvStorage<T, SPARSE_RATIO, I, A>,
+}
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Clone
+    for ReadView<'_, T, SPARSE_RATIO, I, A>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const SPARSE_RATIO: usize, I, A: Allocator + Clone> Copy
+    for ReadView<'_, T, SPARSE_RATIO, I, A>
+{
+}
+
+impl<T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    IdvStorage<T, SPARSE_RATIO, I, A>
+{
+    /// Returns a read-only view that can be shared across threads.
+    pub fn as_read(&self) -> ReadView<'_, T, SPARSE_RATIO, I, A> {
+        ReadView { storage: self }
+    }
+}
+
+impl<'a, T, const SPARSE_RATIO: usize, I: RedirectIndex, A: Allocator + Clone>
+    ReadView<'a, T, SPARSE_RATIO, I, A>
+{
+    /// Returns the number of components stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns `true` if the storage holds no components.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    /// Returns `true` if a component is stored at `idx`.
+    #[inline]
+    pub fn contains(&self, idx: Index) -> bool {
+        self.storage.contains(idx)
+    }
+
+    /// Returns a reference to the component at `idx`, if any.
+    #[inline]
+    pub fn get_checked(&self, idx: Index) -> Option<&'a T> {
+        self.storage.get_checked(idx)
+    }
+
+    /// Iterates the components at the indices set in `mask`.
+    pub fn iter<B>(&self, mask: &'a B) -> Iter<'a, B, T, SPARSE_RATIO, I, A>
+    where
+        B: BitSetLike,
+    {
+        self.storage.iter(mask)
+    }
+}
@@ -0,0 +1,160 @@
+//! `AdaptiveStorage` switching between the sparse and the flat representation.
+
+use specs::hibitset::BitSet;
+use specs::storage::UnprotectedStorage;
+use specs_idvs::{AdaptiveStorage, CapacityError};
+use std::collections::BTreeMap;
+
+type Storage = AdaptiveStorage<u32>;
+
+fn assert_matches(storage: &Storage, model: &BTreeMap<u32, u32>, range: u32) {
+    assert_eq!(storage.len(), model.len());
+
+    for i in 0..range {
+        assert_eq!(storage.get_checked(i), model.get(&i), "index {}", i);
+    }
+}
+
+#[test]
+fn becomes_dense_and_sparse_again() {
+    let mut storage = Storage::default();
+
+    for i in 0..100 {
+        storage.replace(i, i);
+    }
+
+    assert!(storage.is_dense());
+    assert_eq!(storage.threshold(), 0.75);
+
+    // Still dense while occupancy stays at half the threshold or above.
+    for i in 0..62 {
+        assert_eq!(storage.take(i), Some(i));
+    }
+
+    assert!(storage.is_dense());
+    storage.take(62);
+    assert!(!storage.is_dense());
+    assert_eq!(storage.len(), 37);
+
+    for i in 0..100 {
+        assert_eq!(storage.get_checked(i), (i > 62).then_some(&i));
+    }
+}
+
+#[test]
+fn small_ranges_stay_sparse() {
+    let mut storage = Storage::default();
+
+    for i in 0..32 {
+        storage.replace(i, i);
+    }
+
+    assert!(!storage.is_dense());
+}
+
+#[test]
+fn same_results_in_both_modes() {
+    let mut storage = Storage::default();
+    let mut model = BTreeMap::new();
+    let mut modes = [false; 2];
+    let mut x = 0x2545_f491_u32;
+
+    // Mostly inserts first, then mostly removals.
+    for round in 0..10_000 {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        let idx = x % 200;
+
+        if x.is_multiple_of(5) == (round < 5_000) {
+            assert_eq!(storage.take(idx), model.remove(&idx));
+        } else {
+            assert_eq!(storage.replace(idx, x), model.insert(idx, x));
+        }
+
+        modes[storage.is_dense() as usize] = true;
+        assert_eq!(storage.get_checked(idx), model.get(&idx));
+    }
+
+    assert_eq!(modes, [true; 2]);
+    assert!(!storage.is_dense());
+    assert_matches(&storage, &model, 200);
+
+    let mut has = BitSet::new();
+
+    for &idx in model.keys().filter(|&&idx| idx % 2 == 0) {
+        has.add(idx);
+    }
+
+    unsafe { storage.clean(&has) };
+    model.retain(|idx, _| idx % 2 == 1);
+    assert_matches(&storage, &model, 200);
+}
+
+#[test]
+fn threshold_converts_right_away() {
+    let mut storage = Storage::with_threshold(1.0);
+
+    for i in (0..100).step_by(10).chain(0..80) {
+        storage.replace(i, i);
+    }
+
+    assert!(!storage.is_dense());
+    storage.set_threshold(0.5);
+    assert!(storage.is_dense());
+    storage.set_threshold(2.0);
+    assert!(!storage.is_dense());
+    assert_eq!(storage.get_checked(90), Some(&90));
+}
+
+#[test]
+fn far_index_goes_sparse() {
+    let mut storage = Storage::default();
+
+    for i in 0..100 {
+        storage.replace(i, i);
+    }
+
+    assert!(storage.is_dense());
+    storage.replace(1_000_000, 7);
+    assert!(!storage.is_dense());
+    assert_eq!(storage.get_checked(1_000_000), Some(&7));
+    assert_eq!(storage.get_checked(99), Some(&99));
+}
+
+#[test]
+fn far_index_past_sparse_capacity() {
+    // More components than a `u16` storage has internal slots, which only
+    // the flat representation can hold.
+    const LEN: u32 = 70_000;
+
+    let mut storage = Storage::default();
+
+    for i in 0..LEN {
+        storage.try_insert(i, i).unwrap();
+    }
+
+    assert!(storage.is_dense());
+
+    // Neither growing the flat vector to this index nor going sparse works.
+    assert_eq!(storage.try_insert(10_000_000, 1), Err(CapacityError(1)));
+    assert!(storage.is_dense());
+    assert_eq!(storage.len(), LEN as usize);
+    assert!(!storage.contains(10_000_000));
+
+    // Right past the end is still dense enough.
+    storage.try_insert(LEN, LEN).unwrap();
+    assert_eq!(storage.get_checked(LEN), Some(&LEN));
+}
+
+#[test]
+#[should_panic(expected = "no room for a component at index 10000000")]
+fn replace_past_sparse_capacity_panics() {
+    let mut storage = Storage::default();
+
+    for i in 0..70_000 {
+        storage.replace(i, i);
+    }
+
+    storage.replace(10_000_000, 1);
+}
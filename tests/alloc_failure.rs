@@ -0,0 +1,85 @@
+//! Runs `checked_insert` and `try_reserve` under an allocator that refuses
+//! large requests, to check that failed growth is reported instead of
+//! aborting the process.
+
+use specs_idvs::{IdvStorage, InsertError};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Fails every allocation or reallocation larger than `LIMIT` bytes.
+struct Limited;
+
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+unsafe impl GlobalAlloc for Limited {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() > LIMIT.load(Ordering::Relaxed) {
+            return ptr::null_mut();
+        }
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > LIMIT.load(Ordering::Relaxed) {
+            return ptr::null_mut();
+        }
+
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Limited = Limited;
+
+type Big = [u64; 4096];
+
+// A single test, so that no other test allocates while the limit is lowered.
+#[test]
+fn checked_insert_reports_failed_growth() {
+    let mut small: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+    let mut big: IdvStorage<Big> = IdvStorage::default();
+    big.replace(0, [1; 4096]);
+
+    LIMIT.store(1 << 20, Ordering::Relaxed);
+
+    // The redirect table for this index needs far more than the limit.
+    let err = small.checked_insert(16_000_000, 7);
+    // Nor do the data slots for 64 components of 32 KiB.
+    let big_err = (1..64).find_map(|i| {
+        let err = big.checked_insert(i, [2; 4096]).err()?;
+        Some(matches!(err, InsertError::Alloc(v) if v == [2; 4096]))
+    });
+    // Reserving room up front fails the same way.
+    let capacity = big.capacity();
+    let reserve = big.try_reserve(64);
+    let reserved_capacity = big.capacity();
+    // Overwriting and inserting within the reserved room still works.
+    let overwrite = small.checked_insert(3, 30);
+    let vacant = small.checked_insert(10, 100);
+
+    LIMIT.store(usize::MAX, Ordering::Relaxed);
+
+    assert_eq!(err, Err(InsertError::Alloc(7)));
+    assert_eq!(big_err, Some(true));
+    assert!(reserve.is_err());
+    assert_eq!(reserved_capacity, capacity);
+    assert_eq!(overwrite, Ok(Some(3)));
+    assert_eq!(vacant, Ok(None));
+
+    small.audit().unwrap();
+    big.audit().unwrap();
+    assert_eq!(small.len(), 11);
+    assert_eq!(small.get_checked(16_000_000), None);
+    assert_eq!(small.get_checked(3), Some(&30));
+
+    assert_eq!(small.checked_insert(16_000_000, 7), Ok(None));
+    assert_eq!(small.get_checked(16_000_000), Some(&7));
+    big.try_reserve(64).unwrap();
+    assert!(big.capacity() >= 64);
+}
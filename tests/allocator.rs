@@ -0,0 +1,129 @@
+//! Storages allocating through a custom allocator, with the nightly
+//! `allocator_api` feature.
+
+#![cfg(feature = "allocator_api")]
+#![feature(allocator_api)]
+
+use specs::prelude::*;
+use specs_idvs::IdvStorage;
+use std::alloc::{AllocError, Allocator, Global, Layout};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct Counts {
+    allocs: AtomicUsize,
+    live_bytes: AtomicUsize,
+}
+
+/// Forwards to the global allocator and counts what passes through it.
+#[derive(Clone, Default)]
+struct Tracking(Arc<Counts>);
+
+impl Tracking {
+    fn allocs(&self) -> usize {
+        self.0.allocs.load(Ordering::SeqCst)
+    }
+
+    fn live_bytes(&self) -> usize {
+        self.0.live_bytes.load(Ordering::SeqCst)
+    }
+}
+
+unsafe impl Allocator for Tracking {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.allocs.fetch_add(1, Ordering::SeqCst);
+        self.0.live_bytes.fetch_add(layout.size(), Ordering::SeqCst);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.live_bytes.fetch_sub(layout.size(), Ordering::SeqCst);
+        Global.deallocate(ptr, layout)
+    }
+}
+
+type Storage = IdvStorage<u64, 4, u16, Tracking>;
+
+#[test]
+fn buffers_come_from_allocator() {
+    let alloc = Tracking::default();
+    let mut storage = Storage::new_in(alloc.clone());
+    assert_eq!(alloc.allocs(), 0);
+
+    for i in 0..100 {
+        storage.replace(i * 2, u64::from(i));
+    }
+
+    assert!(alloc.allocs() > 0);
+    // With the `smallvec` feature the free list lives outside the allocator.
+    let usage = storage.memory_usage();
+    assert!(alloc.live_bytes() >= usage.redirect_bytes + usage.data_bytes);
+
+    let copy = storage.clone();
+    assert_eq!(copy, storage);
+    drop(storage);
+    assert!(alloc.live_bytes() > 0);
+    drop(copy);
+    assert_eq!(alloc.live_bytes(), 0);
+}
+
+#[test]
+fn operations_keep_allocator() {
+    let alloc = Tracking::default();
+    let mut storage = Storage::with_capacity_in(64, alloc.clone());
+    assert!(storage.capacity() >= 64);
+    let reserved = alloc.allocs();
+    assert!(reserved > 0);
+
+    for i in 0..64 {
+        storage.replace(i, u64::from(i));
+    }
+
+    assert_eq!(alloc.allocs(), reserved);
+
+    for i in (0..64).step_by(2) {
+        storage.take(i);
+    }
+
+    storage.mark_remove(1);
+    storage.sweep();
+    storage.compact();
+    storage.shrink_to_fit();
+    storage.clear();
+    storage.extend((0..10).map(|i| (i, 1)));
+    storage.audit().unwrap();
+
+    let wide = storage.with_sparse_ratio::<16>();
+    let doubled = wide.map_values(|_, v| v * 2);
+    assert_eq!(doubled.get_checked(3), Some(&2));
+    assert!(Arc::ptr_eq(&doubled.allocator().0, &alloc.0));
+
+    drop(doubled);
+    assert_eq!(alloc.live_bytes(), 0);
+}
+
+#[derive(Debug, PartialEq)]
+struct Tracked(u32);
+
+impl Component for Tracked {
+    type Storage = IdvStorage<Self, 4, u16, Tracking>;
+}
+
+#[test]
+fn component_storage_in_world() {
+    let mut world = World::new();
+    world.register::<Tracked>();
+
+    let entities: Vec<_> = (0..20)
+        .map(|i| world.create_entity().with(Tracked(i)).build())
+        .collect();
+    world.delete_entity(entities[5]).unwrap();
+    world.maintain();
+
+    let storage = world.read_storage::<Tracked>();
+    assert_eq!(storage.join().count(), 19);
+    assert_eq!(storage.get(entities[6]), Some(&Tracked(6)));
+    assert!(storage.unprotected_storage().allocator().allocs() > 0);
+}
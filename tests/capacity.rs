@@ -0,0 +1,383 @@
+//! Reserving, reporting and releasing the memory of a storage.
+
+use specs::hibitset::{BitSet, BitSetLike};
+use specs::world::Index;
+use specs_idvs::{IdvStorage, SlotPolicy};
+use std::mem;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn with_capacity_fills_without_reallocating() {
+    const MAX: usize = 5_000;
+
+    let mut storage = IdvStorage::<u64>::with_capacity(MAX);
+    let usage = storage.memory_usage();
+    assert!(storage.capacity() >= MAX);
+    assert_eq!(
+        usage.redirect_bytes,
+        MAX / 4 * std::mem::size_of::<[u16; 4]>()
+    );
+
+    for i in 0..MAX as Index {
+        storage.replace(i, u64::from(i));
+    }
+
+    let filled = storage.memory_usage();
+    assert_eq!(filled.redirect_bytes, usage.redirect_bytes);
+    assert_eq!(filled.data_bytes, usage.data_bytes);
+    assert_eq!(filled.free_list_bytes, usage.free_list_bytes);
+    assert_eq!(storage.len(), MAX);
+}
+
+#[test]
+fn reserve_ahead_of_a_burst() {
+    let mut storage = IdvStorage::<u64>::default();
+    // Covers the indices of the burst with redirect groups.
+    storage.replace(999, 1);
+
+    storage.reserve(500);
+    let usage = storage.memory_usage();
+    assert!(storage.capacity() >= 501);
+
+    // Enough room already, nothing changes.
+    storage.reserve(100);
+    assert_eq!(storage.memory_usage(), usage);
+
+    for i in 0..500 {
+        storage.replace(i, u64::from(i));
+    }
+
+    let filled = storage.memory_usage();
+    assert_eq!(filled.redirect_bytes, usage.redirect_bytes);
+    assert_eq!(filled.data_bytes, usage.data_bytes);
+    assert_eq!(filled.free_list_bytes, usage.free_list_bytes);
+}
+
+#[test]
+fn reserve_covers_the_redirect_groups() {
+    let mut storage = IdvStorage::<u64>::default();
+    storage.reserve(1_000);
+    let usage = storage.memory_usage();
+    assert!(usage.redirect_bytes >= 250 * 4 * 2);
+
+    for i in 0..1_000 {
+        storage.replace(i, u64::from(i));
+    }
+
+    let filled = storage.memory_usage();
+    assert_eq!(filled.redirect_bytes, usage.redirect_bytes);
+    assert_eq!(filled.data_bytes, usage.data_bytes);
+}
+
+#[test]
+fn shrink_to_fit_after_mass_removal() {
+    let mut storage: IdvStorage<u64> = (0..10_000).map(|i| (i, u64::from(i))).collect();
+    let peak = storage.memory_usage();
+
+    // The survivors sit in high internal slots and at low indices.
+    storage.retain(|idx, _| idx % 100 == 7 && idx < 5_000);
+    storage.shrink_to_fit();
+
+    let usage = storage.memory_usage();
+    assert_eq!(storage.len(), 50);
+    assert_eq!(storage.capacity(), 50);
+    assert!(usage.data_bytes * 100 <= peak.data_bytes);
+    assert!(usage.redirect_bytes * 2 <= peak.redirect_bytes);
+    assert_eq!(usage.free_slots, 0);
+
+    for i in 0..10_000 {
+        let expected = (i % 100 == 7 && i < 5_000).then(|| u64::from(i));
+        assert_eq!(storage.get_checked(i).copied(), expected);
+    }
+
+    storage.audit().unwrap();
+}
+
+#[test]
+fn capacity_grows_with_reserve() {
+    let mut storage = IdvStorage::<u64>::default();
+    assert_eq!(storage.capacity(), 0);
+
+    storage.reserve(100);
+    assert!(storage.capacity() >= 100);
+
+    for i in 0..1_000 {
+        storage.replace(i * 7, 1);
+        assert!(storage.len() <= storage.capacity());
+
+        if i % 3 == 0 {
+            storage.take(i * 7 / 2);
+            assert!(storage.len() <= storage.capacity());
+        }
+    }
+
+    storage.reserve(5_000);
+    assert!(storage.capacity() >= storage.len() + 5_000);
+}
+
+#[test]
+fn growth_is_geometric() {
+    fn grows_for(n: Index) -> usize {
+        let grows = Arc::new(AtomicUsize::new(0));
+        let counter = grows.clone();
+        let mut storage = IdvStorage::<u32, 4, u32>::default();
+        storage.on_grow(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        for i in 0..n {
+            storage.replace(i, i);
+        }
+
+        grows.load(Ordering::Relaxed)
+    }
+
+    let small = grows_for(1_000);
+    let large = grows_for(1_000_000);
+
+    // Each grow adds half the slots, so a thousand times the components
+    // take about log1.5(1000) = 17 more grows, not a thousand times as many.
+    assert!(small > 0);
+    assert!(large < small + 25, "{} grows, {} for 1000", large, small);
+    assert!(large <= 40, "{}", large);
+}
+
+#[test]
+fn memory_usage_matches_capacities() {
+    let mut storage = IdvStorage::<u64>::with_capacity(1_000);
+
+    for i in 0..300 {
+        storage.replace(i * 3, 1);
+    }
+
+    storage.take(3);
+    let usage = storage.memory_usage();
+    let slot_size = if cfg!(feature = "cache-aligned") {
+        64
+    } else {
+        8
+    };
+
+    assert_eq!(usage.redirect_bytes, 250 * std::mem::size_of::<[u16; 4]>());
+    assert_eq!(usage.data_bytes, storage.capacity() * slot_size);
+    assert!(usage.free_list_bytes >= usage.free_slots * 2);
+    assert_eq!(usage.occupied_slots, 299);
+    assert!(usage.occupied_slots + usage.free_slots <= storage.capacity());
+    assert_eq!(
+        usage.total_bytes(),
+        usage.redirect_bytes + usage.data_bytes + usage.free_list_bytes + usage.occupancy_bytes
+    );
+    assert!(format!("{:?}", usage).starts_with("MemoryStats { redirect_bytes: 2000,"));
+}
+
+#[test]
+fn freeze_releases_spare_memory() {
+    let mut storage = IdvStorage::<u64>::with_capacity(1_000);
+
+    for i in 0..1_000 {
+        storage.replace(i, u64::from(i));
+    }
+
+    storage.retain(|idx, _| idx % 4 == 0);
+    let usage = storage.memory_usage();
+    let frozen = storage.freeze();
+    let frozen_usage = frozen.memory_usage();
+    assert!(frozen_usage.total_bytes() < usage.total_bytes());
+    assert_eq!(frozen_usage.free_slots, 0);
+
+    assert_eq!(frozen.len(), 250);
+    assert_eq!(frozen.keys().count(), 250);
+
+    for i in 0..1_000 {
+        let expected = (i % 4 == 0).then(|| u64::from(i));
+        assert_eq!(frozen.get_checked(i).copied(), expected, "{}", i);
+    }
+
+    assert_eq!(frozen.values().sum::<u64>(), (0..1_000).step_by(4).sum());
+
+    let mut storage = frozen.thaw();
+    storage.replace(1, 1);
+    assert_eq!(storage.len(), 251);
+    storage.audit().unwrap();
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Marker;
+
+#[test]
+fn zero_sized_components_allocate_no_data() {
+    let mut storage = IdvStorage::<Marker>::default();
+
+    for i in 0..10_000 {
+        storage.replace(i * 2, Marker);
+    }
+
+    assert_eq!(storage.memory_usage().data_bytes, 0);
+    assert_eq!(storage.len(), 10_000);
+
+    for i in 0..20 {
+        assert_eq!(storage.get_checked(i), (i % 2 == 0).then_some(&Marker));
+    }
+
+    assert_eq!(storage.take(4), Some(Marker));
+    assert_eq!(storage.take(4), None);
+    assert!(!storage.contains(4));
+    storage.replace(5, Marker);
+    assert_eq!(storage.len(), 10_000);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn on_grow_reports_each_new_size() {
+    let sizes = Arc::new(Mutex::new(Vec::new()));
+    let seen = sizes.clone();
+    let mut storage = IdvStorage::<u32, 4, u32>::default();
+    storage.on_grow(move |slots| seen.lock().unwrap().push(slots));
+
+    for i in 0..100_000 {
+        storage.replace(i, i);
+    }
+
+    // Every call reports the slot count after growing, starting from the
+    // minimum growth and ending with all slots the fill needed.
+    let sizes = sizes.lock().unwrap();
+    assert_eq!(sizes.first(), Some(&8));
+    assert_eq!(sizes.last(), Some(&storage.capacity()));
+    assert!(storage.capacity() >= 100_000);
+    assert!(sizes.windows(2).all(|w| w[0] < w[1] && w[1] <= w[0] * 2));
+    assert!(sizes.len() < 40, "{:?}", sizes);
+}
+
+#[test]
+fn shrink_free_list_after_mass_removal() {
+    let mut storage = IdvStorage::<u32>::default();
+
+    for i in 0..10_000 {
+        storage.replace(i, i);
+    }
+
+    // Keeping the last component stops the free slots from being trimmed.
+    for i in 0..9_999 {
+        storage.take(i);
+    }
+
+    for i in 0..9_990 {
+        storage.replace(i, i);
+    }
+
+    let inflated = storage.memory_usage();
+    storage.shrink_free_list();
+    let usage = storage.memory_usage();
+    assert!(usage.free_list_bytes * 3 < inflated.free_list_bytes);
+    assert_eq!(usage.free_slots, inflated.free_slots);
+
+    // The remaining free slots are still handed out before growing.
+    let capacity = storage.capacity();
+
+    for i in 9_990..9_999 {
+        storage.replace(i, i);
+    }
+
+    assert_eq!(storage.capacity(), capacity);
+    assert_eq!(storage.len(), 10_000);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn reserve_from_mask_covers_the_join() {
+    let mut mask = BitSet::new();
+
+    for i in (0..20_000).step_by(7) {
+        mask.add(i);
+    }
+
+    let mut storage = IdvStorage::<u64>::default();
+    storage.replace(3, 3);
+    storage.reserve_from_mask(&mask);
+    let (capacity, usage) = (storage.capacity(), storage.memory_usage());
+
+    for i in mask.iter() {
+        storage.replace(i, u64::from(i));
+    }
+
+    // Slots are added from the reserved room, the buffers stay the same.
+    assert_eq!(storage.capacity(), capacity);
+    assert_eq!(storage.memory_usage().redirect_bytes, usage.redirect_bytes);
+    assert_eq!(storage.memory_usage().data_bytes, usage.data_bytes);
+    assert_eq!(storage.len(), 2_859);
+}
+
+#[test]
+fn builder_option_combinations() {
+    let default = IdvStorage::<u32>::builder().build();
+    assert_eq!(default.slot_policy(), SlotPolicy::Lifo);
+    assert_eq!(default.capacity(), 0);
+
+    let reserved = IdvStorage::<u32, 8>::builder()
+        .capacity(1_000)
+        .lowest_first(true)
+        .build();
+    assert!(reserved.capacity() >= 1_000);
+    assert_eq!(reserved.slot_policy(), SlotPolicy::LowestFirst);
+    assert_eq!(
+        IdvStorage::<u32>::builder()
+            .lowest_first(false)
+            .build()
+            .slot_policy(),
+        SlotPolicy::Lifo
+    );
+
+    // The first insert grows by at least the configured batch.
+    let mut batched = IdvStorage::<u32, 4, u32>::builder()
+        .growth(1_000)
+        .slot_policy(SlotPolicy::LowestFirst)
+        .build();
+    batched.replace(0, 0);
+    assert!(batched.capacity() >= 1_000);
+    assert_eq!(batched.memory_usage().free_slots, batched.capacity() - 1);
+
+    // A zero batch still grows one slot at a time.
+    let mut single = IdvStorage::<u32>::builder().growth(0).build();
+    single.replace(0, 0);
+    single.replace(1, 1);
+    assert_eq!(single.len(), 2);
+
+    let mut lowest = IdvStorage::<u32>::builder()
+        .lowest_first(true)
+        .growth(16)
+        .build();
+    lowest.extend((0..16).map(|i| (i, i)));
+    lowest.take(9);
+    lowest.take(3);
+    lowest.replace(100, 100);
+    assert_eq!(lowest.internal_slot(100), Some(3));
+}
+
+fn bytes_per_slot<T>(v: impl Fn(Index) -> T) -> usize {
+    let mut storage = IdvStorage::<T>::with_capacity(64);
+
+    for i in (0..64).step_by(2) {
+        storage.replace(i, v(i));
+    }
+
+    assert_eq!(storage.len(), 32);
+    assert!(storage.contains(62) && !storage.contains(63));
+    storage.memory_usage().data_bytes / storage.capacity()
+}
+
+#[test]
+fn slots_carry_no_discriminant() {
+    // Occupancy lives in a bitset, so neither a type with a niche nor one
+    // without pays for an `Option` around it.
+    let niche = bytes_per_slot(|i| NonZeroU32::new(i + 1).unwrap());
+    let plain = bytes_per_slot(|i| i);
+
+    if cfg!(feature = "cache-aligned") {
+        assert_eq!((niche, plain), (64, 64));
+    } else {
+        assert_eq!((niche, plain), (4, 4));
+        assert!(mem::size_of::<Option<u32>>() > plain);
+    }
+}
@@ -0,0 +1,42 @@
+//! Release-mode access checks of the `UnprotectedStorage` impl, with the
+//! `checked` feature.
+
+#![cfg(feature = "checked")]
+
+use specs::storage::UnprotectedStorage;
+use specs_idvs::IdvStorage;
+
+fn storage() -> IdvStorage<u32> {
+    (0..10).map(|i| (i, i)).collect()
+}
+
+#[test]
+#[should_panic(expected = "no component at index 1000000, mask and storage out of sync?")]
+fn get_past_groups_panics() {
+    unsafe { storage().get(1_000_000) };
+}
+
+#[test]
+#[should_panic(expected = "no component at index 11, mask and storage out of sync?")]
+fn get_mut_vacant_panics() {
+    unsafe { storage().get_mut(11) };
+}
+
+#[test]
+#[should_panic(expected = "no component to remove at index 1000000")]
+fn remove_past_groups_panics() {
+    unsafe { storage().remove(1_000_000) };
+}
+
+#[test]
+fn valid_access_is_unaffected() {
+    let mut storage = storage();
+
+    unsafe {
+        *storage.get_mut(3) += 1;
+        assert_eq!(*storage.get(3), 4);
+        assert_eq!(storage.remove(3), 4);
+    }
+
+    assert!(!storage.contains(3));
+}
@@ -0,0 +1,82 @@
+//! `content_checksum` must agree between peers, whatever the layout of their
+//! storages and whatever platform they run on.
+
+use specs_idvs::IdvStorage;
+
+/// FNV-1a over raw bytes, to spell out what the checksum covers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[test]
+fn known_value() {
+    let storage: IdvStorage<u32> = vec![(1, 10), (7, 70)].into_iter().collect();
+
+    // The length widened to 64 bits, then every pair, all little endian.
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&2u64.to_le_bytes());
+
+    for (idx, v) in [(1u32, 10u32), (7, 70)] {
+        bytes.extend_from_slice(&idx.to_le_bytes());
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    assert_eq!(storage.content_checksum(), fnv1a(&bytes));
+    assert_eq!(storage.content_checksum(), 0x6208_213a_9cdd_3e6d);
+}
+
+#[test]
+fn native_width_values_are_portable() {
+    let storage: IdvStorage<(usize, isize)> = vec![(0, (1, -1))].into_iter().collect();
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&1u64.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&1u64.to_le_bytes());
+    bytes.extend_from_slice(&(-1i64).to_le_bytes());
+
+    assert_eq!(storage.content_checksum(), fnv1a(&bytes));
+}
+
+#[test]
+fn independent_of_layout() {
+    let dense: IdvStorage<u32> = (0..100).map(|i| (i, i * 2)).collect();
+
+    let mut churned = IdvStorage::<u32>::default();
+
+    for i in (0..300).rev() {
+        churned.replace(i, 0);
+    }
+
+    for i in 100..300 {
+        churned.take(i);
+    }
+
+    for i in 0..100 {
+        churned.replace(i, i * 2);
+    }
+
+    assert_ne!(dense.internal_slot(5), churned.internal_slot(5));
+    assert_eq!(dense.content_checksum(), churned.content_checksum());
+    assert_eq!(dense.content_checksum(), dense.clone().content_checksum());
+}
+
+#[test]
+fn sensitive_to_contents() {
+    let storage: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+    let checksum = storage.content_checksum();
+
+    let mut changed = storage.clone();
+    changed[3] = 4;
+    assert_ne!(changed.content_checksum(), checksum);
+
+    let mut moved = storage.clone();
+    assert!(moved.move_index(9, 10));
+    assert_ne!(moved.content_checksum(), checksum);
+
+    let mut shorter = storage;
+    shorter.take(0);
+    assert_ne!(shorter.content_checksum(), checksum);
+}
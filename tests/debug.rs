@@ -0,0 +1,50 @@
+//! `Debug` output and the other human-readable renderings of a storage.
+
+use specs_idvs::IdvStorage;
+
+#[test]
+fn debug_summarizes_occupancy() {
+    let mut storage: IdvStorage<&str> = vec![(1, "a"), (6, "b"), (9, "c")].into_iter().collect();
+    storage.take(6);
+    let usage = storage.memory_usage();
+    let slots = usage.occupied_slots + usage.free_slots;
+
+    let summary = format!("{:?}", storage);
+    assert_eq!(
+        summary,
+        format!(
+            "IdvStorage {{ len: 2, groups: 3, slots: {}, free_slots: {} }}",
+            slots, usage.free_slots
+        )
+    );
+    assert!(!summary.contains("\"a\""));
+
+    let full = format!("{:#?}", storage);
+    assert!(full.contains("len: 2"), "{}", full);
+    assert!(full.contains("components: {\n        1: \"a\",\n        9: \"c\",\n    },"));
+    assert!(!full.contains("\"b\""));
+}
+
+#[test]
+fn dump_layout_shows_redirects_and_free_list() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(1, 10);
+    storage.replace(6, 60);
+    storage.replace(2, 20);
+    storage.take(1);
+
+    assert_eq!(
+        storage.dump_layout(),
+        "group 0 [-, -, 2, -]\n\
+         group 1 [-, -, 1, -]\n\
+         slots .##.....\n\
+         free [7, 6, 5, 4, 3, 0]\n"
+    );
+
+    // A component marked for removal leaves its slot vacant but off the
+    // free list until the sweep.
+    storage.mark_remove(6);
+    assert!(storage
+        .dump_layout()
+        .ends_with("slots ..#.....\nfree [7, 6, 5, 4, 3, 0]\nmarked [1]\n"));
+}
@@ -0,0 +1,51 @@
+//! The `Idv` alias in specs' `#[storage(...)]` attribute, used the same way
+//! as `VecStorage`.
+
+use specs::prelude::*;
+use specs_derive::Component;
+use specs_idvs::{Idv, IdvStorage};
+
+#[derive(Component, Debug, PartialEq)]
+#[storage(Idv)]
+struct Health(u32);
+
+#[derive(Component, Debug, PartialEq)]
+#[storage(VecStorage)]
+struct Armor(u32);
+
+#[test]
+fn derived_storage_is_idv() {
+    fn storage_of<C: Component>(_: &C::Storage) {}
+
+    let storage = IdvStorage::<Health>::default();
+    storage_of::<Health>(&storage);
+}
+
+#[test]
+fn derived_component_in_world() {
+    let mut world = World::new();
+    world.register::<Health>();
+    world.register::<Armor>();
+
+    let entities: Vec<_> = (0..10)
+        .map(|i| {
+            let builder = world.create_entity().with(Health(i));
+
+            if i % 2 == 0 {
+                builder.with(Armor(i * 2)).build()
+            } else {
+                builder.build()
+            }
+        })
+        .collect();
+
+    world.delete_entity(entities[4]).unwrap();
+    world.maintain();
+
+    let health = world.read_storage::<Health>();
+    let armor = world.read_storage::<Armor>();
+    let joined: Vec<_> = (&health, &armor).join().map(|(h, a)| (h.0, a.0)).collect();
+    assert_eq!(joined, vec![(0, 0), (2, 4), (6, 12), (8, 16)]);
+    assert_eq!(health.get(entities[3]), Some(&Health(3)));
+    assert_eq!(health.get(entities[4]), None);
+}
@@ -0,0 +1,118 @@
+//! The entry API and the other get-or-insert shortcuts.
+
+use specs::storage::UnprotectedStorage;
+use specs_idvs::{Entry, IdvStorage};
+
+#[test]
+fn vacant_then_modify() {
+    let mut storage = IdvStorage::<Vec<u32>>::default();
+
+    let v = storage
+        .entry(5)
+        .and_modify(|v| v.push(0))
+        .or_insert_with(Vec::new);
+    v.push(1);
+    storage
+        .entry(5)
+        .and_modify(|v| v.push(2))
+        .or_insert(vec![9]);
+    assert_eq!(storage.get_checked(5), Some(&vec![1, 2]));
+
+    storage.entry(6).or_default().push(3);
+    assert_eq!(storage.get_checked(6), Some(&vec![3]));
+    assert_eq!(storage.len(), 2);
+
+    match storage.entry(7) {
+        Entry::Vacant(entry) => {
+            assert_eq!(entry.key(), 7);
+            entry.insert(vec![7]);
+        }
+        Entry::Occupied(_) => panic!("index 7 is vacant"),
+    }
+
+    assert_eq!(storage.len(), 3);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn occupied_then_modify() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+
+    // The occupied value wins over the one offered.
+    *storage.entry(3).or_insert(0) += 1;
+    assert_eq!(storage.get_checked(3), Some(&31));
+
+    match storage.entry(3) {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(entry.key(), 3);
+            assert_eq!(*entry.get(), 31);
+            *entry.get_mut() += 1;
+            assert_eq!(entry.insert(40), 32);
+            assert_eq!(entry.remove(), 40);
+        }
+        Entry::Vacant(_) => panic!("index 3 is occupied"),
+    }
+
+    assert!(storage.is_empty());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn get_or_insert_with_present_and_absent() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+
+    *storage.get_or_insert_with(3, || panic!("index 3 is occupied")) += 1;
+    assert_eq!(storage.get_checked(3), Some(&31));
+
+    let mut calls = 0;
+
+    for _ in 0..3 {
+        *storage.get_or_insert_with(9, || {
+            calls += 1;
+            100
+        }) += 1;
+    }
+
+    assert_eq!(calls, 1);
+    assert_eq!(storage.get_checked(9), Some(&103));
+    assert_eq!(storage.len(), 2);
+}
+
+#[test]
+fn get_or_default_inserts_once() {
+    let mut storage: IdvStorage<Vec<u32>> = vec![(3, vec![30])].into_iter().collect();
+
+    storage.get_or_default(8).push(1);
+    storage.get_or_default(8).push(2);
+    storage.get_or_default(3).push(31);
+
+    assert_eq!(storage.get_checked(8), Some(&vec![1, 2]));
+    assert_eq!(storage.get_checked(3), Some(&vec![30, 31]));
+    assert_eq!(unsafe { storage.get(8) }, &vec![1, 2]);
+    assert_eq!(storage.len(), 2);
+
+    assert!(storage.get_or_default(1_000).is_empty());
+    assert_eq!(storage.len(), 3);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn insert_default_reports_replacement() {
+    let mut storage = IdvStorage::<Vec<u32>>::default();
+
+    for i in (0..20).step_by(4) {
+        assert!(!storage.insert_default(i));
+    }
+
+    assert_eq!(storage.len(), 5);
+    assert!(storage.keys().eq((0..20).step_by(4)));
+    assert!(storage.values().all(Vec::is_empty));
+
+    storage.get_checked_mut(8).unwrap().push(1);
+    let slot = storage.internal_slot(8);
+    assert!(storage.insert_default(8));
+    assert_eq!(storage.get_checked(8), Some(&vec![]));
+    assert_eq!(storage.internal_slot(8), slot);
+    assert_eq!(storage.len(), 5);
+    storage.audit().unwrap();
+}
@@ -0,0 +1,60 @@
+//! Every fallible operation reporting through `IdvError`.
+
+use specs::world::Index;
+use specs_idvs::{AuditError, FixedIdvStorage, IdvError, IdvStorage};
+use std::error::Error;
+
+fn insert_all(storage: &mut IdvStorage<u32>, n: Index) -> Result<(), IdvError> {
+    for i in 0..n {
+        storage.try_insert(i, i)?;
+    }
+
+    storage.audit()?;
+    Ok(())
+}
+
+#[test]
+fn capacity_exhausted() {
+    let mut storage = IdvStorage::<u32>::default();
+    assert!(insert_all(&mut storage, 1_000).is_ok());
+
+    let err = insert_all(&mut storage, u16::MAX as Index + 1).unwrap_err();
+    assert!(matches!(err, IdvError::CapacityExhausted));
+    assert_eq!(err.to_string(), "storage has no free internal slots left");
+    assert!(err.source().is_none());
+
+    let err = IdvError::from(storage.checked_insert(100_000, 1).unwrap_err());
+    assert!(matches!(err, IdvError::CapacityExhausted));
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn alloc_failed() {
+    // Reserving every internal slot for a terabyte component overflows.
+    let mut storage = IdvStorage::<[u8; 1 << 40], 4, u32>::default();
+    let err = storage.try_reserve(usize::MAX).unwrap_err();
+    assert!(matches!(err, IdvError::AllocFailed));
+    assert_eq!(err.to_string(), "failed to allocate room for a component");
+    assert_eq!(storage.capacity(), 0);
+}
+
+#[test]
+fn out_of_range() {
+    let mut fixed = FixedIdvStorage::<u32, 8>::new();
+    let err = IdvError::from(fixed.try_insert(8, 1).unwrap_err());
+    assert_eq!(err, IdvError::OutOfRange(8));
+    assert_eq!(err.to_string(), "index 8 is out of range");
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn invariant_violated() {
+    let audit = AuditError::LengthMismatch {
+        len: 3,
+        occupied: 2,
+    };
+    let err = IdvError::from(audit.clone());
+    assert!(matches!(err, IdvError::InvariantViolated(ref e) if *e == audit));
+    assert!(err.to_string().starts_with("storage invariant violated: "));
+    assert_eq!(err.source().unwrap().to_string(), audit.to_string());
+}
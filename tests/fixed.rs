@@ -0,0 +1,144 @@
+//! The inline `FixedIdvStorage`, which shares its redirect bookkeeping with
+//! `IdvStorage`.
+
+use specs::hibitset::BitSet;
+use specs::storage::UnprotectedStorage;
+use specs_idvs::{FixedIdvStorage, InsertError};
+use std::sync::Mutex;
+
+type Storage = FixedIdvStorage<String, 8>;
+
+// `new` is a `const fn`, so storages are built at compile time, including
+// whole arrays of them and storages living in statics.
+const EMPTY: FixedIdvStorage<u32, 16> = FixedIdvStorage::new();
+const PER_CHUNK: [FixedIdvStorage<u32, 16>; 4] = [EMPTY; 4];
+static GLOBAL: Mutex<FixedIdvStorage<u32, 16>> = Mutex::new(FixedIdvStorage::new());
+static ARRAY: Mutex<[FixedIdvStorage<u32, 16>; 4]> = Mutex::new(PER_CHUNK);
+
+#[test]
+fn fill_to_capacity() {
+    let mut storage = Storage::new();
+    assert_eq!(storage.capacity(), 8);
+
+    for i in 0..8 {
+        assert_eq!(storage.replace(i, i.to_string()), None);
+    }
+
+    assert_eq!(storage.len(), 8);
+    assert_eq!(storage.replace(7, "seven".into()), Some("7".into()));
+    assert_eq!(storage.len(), 8);
+
+    for i in 0..7 {
+        assert_eq!(storage.get_checked(i), Some(&i.to_string()));
+    }
+}
+
+#[test]
+fn overflow_returns_value() {
+    let mut storage = Storage::new();
+    storage.try_insert(7, "last".into()).unwrap();
+
+    match storage.try_insert(8, "past".into()) {
+        Err(InsertError::OutOfRange(8, v)) => assert_eq!(v, "past"),
+        other => panic!("inserted past the capacity: {:?}", other),
+    }
+
+    assert_eq!(storage.len(), 1);
+    assert!(!storage.contains(8));
+    assert_eq!(storage.get_checked(u32::MAX), None);
+    assert_eq!(storage.take(8), None);
+}
+
+#[test]
+#[should_panic(expected = "index 8 is out of bounds for 8 slots")]
+fn replace_past_capacity_panics() {
+    Storage::new().replace(8, String::new());
+}
+
+#[test]
+fn take_and_reuse_slots() {
+    let mut storage = Storage::new();
+
+    for i in 0..8 {
+        storage.replace(i, i.to_string());
+    }
+
+    assert_eq!(storage.take(2), Some("2".into()));
+    assert_eq!(storage.take(2), None);
+    assert_eq!(storage.take(5), Some("5".into()));
+    assert!(!storage.contains(5));
+
+    // Every slot is in use or free again, so the full range still fits.
+    storage.replace(5, "five".into());
+    storage.replace(2, "two".into());
+    assert_eq!(storage.len(), 8);
+    assert_eq!(storage.get_checked(2).map(String::as_str), Some("two"));
+    assert_eq!(storage.get_checked(5).map(String::as_str), Some("five"));
+
+    storage.get_checked_mut(0).unwrap().push('!');
+    assert_eq!(storage.get_checked(0).map(String::as_str), Some("0!"));
+}
+
+#[test]
+fn clean_and_clear() {
+    let mut storage = Storage::new();
+
+    for i in 0..8 {
+        storage.replace(i, i.to_string());
+    }
+
+    let mut has = BitSet::new();
+
+    for i in [1, 3, 6, 20] {
+        has.add(i);
+    }
+
+    unsafe {
+        storage.clean(&has);
+        assert_eq!(storage.remove(0), "0");
+        assert_eq!(storage.get(7), "7");
+    }
+
+    assert_eq!(storage.len(), 4);
+    assert!(!storage.contains(3));
+
+    storage.clear();
+    assert!(storage.is_empty());
+    storage.replace(4, "again".into());
+    assert_eq!(storage.len(), 1);
+}
+
+#[test]
+fn u32_redirects() {
+    let mut storage = FixedIdvStorage::<u8, 300, u32>::new();
+
+    for i in 0..300 {
+        storage.replace(i, i as u8);
+    }
+
+    assert_eq!(storage.take(299), Some(43));
+    assert_eq!(storage.get_checked(256), Some(&0));
+}
+
+#[test]
+fn const_and_static_storages() {
+    let mut chunks = PER_CHUNK;
+
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        assert!(chunk.is_empty());
+        chunk.replace(i as u32, i as u32 * 10);
+    }
+
+    assert_eq!(chunks[3].get_checked(3), Some(&30));
+    assert!(!chunks[0].contains(3));
+    assert!(EMPTY.is_empty());
+
+    let mut global = GLOBAL.lock().unwrap();
+    global.replace(15, 1);
+    assert_eq!(global.get_checked(15), Some(&1));
+    drop(global);
+
+    let mut array = ARRAY.lock().unwrap();
+    array[2].replace(0, 2);
+    assert_eq!(array.iter().map(|s| s.len()).sum::<usize>(), 1);
+}
@@ -0,0 +1,47 @@
+//! `FlaggedIdvStorage` reporting component changes to registered readers.
+
+use specs::prelude::*;
+use specs::storage::ComponentEvent;
+use specs_idvs::FlaggedIdvStorage;
+
+#[derive(Debug, PartialEq)]
+struct Health(u32);
+
+impl Component for Health {
+    type Storage = FlaggedIdvStorage<Self>;
+}
+
+#[test]
+fn events_for_insert_get_mut_and_remove() {
+    let mut world = World::new();
+    world.register::<Health>();
+    let mut reader = world.write_storage::<Health>().register_reader();
+
+    let a = world.create_entity().with(Health(10)).build();
+    let b = world.create_entity().with(Health(20)).build();
+
+    {
+        let mut storage = world.write_storage::<Health>();
+        storage.get_mut(b).unwrap().0 -= 5;
+        // Reading does not flag anything.
+        assert_eq!(storage.get(a), Some(&Health(10)));
+        storage.remove(a);
+    }
+
+    let events: Vec<_> = world
+        .read_storage::<Health>()
+        .channel()
+        .read(&mut reader)
+        .cloned()
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ComponentEvent::Inserted(a.id()),
+            ComponentEvent::Inserted(b.id()),
+            ComponentEvent::Modified(b.id()),
+            ComponentEvent::Removed(a.id()),
+        ]
+    );
+    assert_eq!(world.read_storage::<Health>().get(b), Some(&Health(15)));
+}
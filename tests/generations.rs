@@ -0,0 +1,131 @@
+//! Stale handle detection through `get_generational`, with the
+//! `generations` feature.
+
+#![cfg(feature = "generations")]
+
+use specs_idvs::IdvStorage;
+
+#[test]
+fn removal_invalidates_handle() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(3, 30);
+    let gen = storage.generation(3).unwrap();
+    assert_eq!(storage.get_generational(3, gen), Some(&30));
+
+    storage.take(3);
+    assert_eq!(storage.generation(3), None);
+    assert_eq!(storage.get_generational(3, gen), None);
+
+    storage.replace(3, 31);
+    assert_eq!(storage.get_generational(3, gen), None);
+
+    let gen = storage.generation(3).unwrap();
+    *storage.get_generational_mut(3, gen).unwrap() += 1;
+    assert_eq!(storage[3], 32);
+}
+
+#[test]
+fn recycled_slot_rejects_stale_handle() {
+    let mut storage = IdvStorage::<&str>::default();
+    storage.replace(0, "old");
+    let handle = (0, storage.generation(0).unwrap());
+    storage.replace(1, "one");
+    storage.take(0);
+
+    // Index 2 reuses the slot index 0 vacated, so index 0 lands in a fresh
+    // slot that was never removed from.
+    storage.replace(2, "two");
+    storage.replace(0, "new");
+    assert_eq!(storage.internal_slot(2), Some(0));
+    assert_ne!(storage.internal_slot(0), Some(0));
+
+    assert_eq!(storage.get_generational(handle.0, handle.1), None);
+    assert_eq!(storage.get_generational_mut(handle.0, handle.1), None);
+}
+
+#[test]
+fn same_slot_reuse_rejects_stale_handle() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(5, 50);
+    let gen = storage.generation(5).unwrap();
+    let slot = storage.internal_slot(5);
+
+    storage.take(5);
+    storage.replace(5, 51);
+    assert_eq!(storage.internal_slot(5), slot);
+    assert_eq!(storage.get_generational(5, gen), None);
+}
+
+#[test]
+fn overwrite_keeps_generation() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(1, 10);
+    let gen = storage.generation(1).unwrap();
+    storage.replace(1, 11);
+    assert_eq!(storage.get_generational(1, gen), Some(&11));
+}
+
+#[test]
+fn moving_components_invalidates_handles() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(1, 10);
+    storage.replace(2, 20);
+    let (gen1, gen2) = (
+        storage.generation(1).unwrap(),
+        storage.generation(2).unwrap(),
+    );
+
+    storage.swap(1, 2);
+    assert_eq!(storage.get_generational(1, gen1), None);
+    assert_eq!(storage.get_generational(2, gen2), None);
+
+    let gen1 = storage.generation(1).unwrap();
+    assert!(storage.move_index(1, 7));
+    storage.replace(1, 11);
+    assert_eq!(storage.get_generational(1, gen1), None);
+}
+
+#[test]
+fn clear_and_trim_invalidate_handles() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(2, 20);
+    storage.replace(400, 4000);
+    let (gen2, gen400) = (
+        storage.generation(2).unwrap(),
+        storage.generation(400).unwrap(),
+    );
+
+    // Removing index 400 trims its redirect group away.
+    storage.take(400);
+    storage.replace(400, 4001);
+    assert_eq!(storage.get_generational(400, gen400), None);
+
+    storage.clear();
+    storage.replace(2, 21);
+    assert_eq!(storage.get_generational(2, gen2), None);
+}
+
+#[test]
+fn relocation_keeps_handles() {
+    let mut storage = IdvStorage::<u32>::default();
+
+    for i in 0..32 {
+        storage.replace(i, i);
+    }
+
+    for i in 0..16 {
+        storage.take(i);
+    }
+
+    let gens: Vec<_> = (16..32).map(|i| storage.generation(i).unwrap()).collect();
+    storage.compact();
+    storage.sort_by_index();
+    storage.shrink_to_fit();
+
+    for (i, gen) in (16..32).zip(gens) {
+        assert_eq!(storage.get_generational(i, gen), Some(&i));
+    }
+
+    let copy = storage.clone();
+    assert_eq!(copy.generation(20), storage.generation(20));
+}
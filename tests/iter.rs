@@ -0,0 +1,377 @@
+//! The iterators over a storage, and the removals driven by them.
+
+use specs::hibitset::BitSet;
+use specs::world::Index;
+use specs_idvs::IdvStorage;
+
+fn mask(indices: impl IntoIterator<Item = Index>) -> BitSet {
+    let mut mask = BitSet::new();
+
+    for idx in indices {
+        mask.add(idx);
+    }
+
+    mask
+}
+
+fn strings(indices: impl IntoIterator<Item = Index>) -> IdvStorage<String> {
+    indices.into_iter().map(|i| (i, i.to_string())).collect()
+}
+
+#[test]
+fn drain_fully_and_partially() {
+    let mut storage = strings([9, 2, 40, 5]);
+    let capacity = storage.capacity();
+
+    let drained: Vec<_> = storage.drain().collect();
+    assert_eq!(drained, strings([2, 5, 9, 40]).into_vec());
+    assert!(storage.is_empty());
+    assert_eq!(storage.capacity(), capacity);
+    storage.audit().unwrap();
+
+    storage.extend((0..10).map(|i| (i, i.to_string())));
+    let capacity = storage.capacity();
+    let mut drain = storage.drain();
+    assert_eq!(drain.len(), 10);
+    assert_eq!(drain.next(), Some((0, "0".into())));
+    assert_eq!(drain.next(), Some((1, "1".into())));
+    assert_eq!(drain.len(), 8);
+    drop(drain);
+
+    // The rest went with the iterator and the storage is usable again.
+    assert!(storage.is_empty());
+    assert_eq!(storage.get_checked(5), None);
+    storage.audit().unwrap();
+    storage.replace(3, "three".into());
+    assert_eq!(storage.len(), 1);
+
+    drop(storage.drain());
+    assert!(storage.is_empty());
+    assert_eq!(storage.capacity(), capacity);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn retain_even_values() {
+    let mut storage: IdvStorage<u32> = (0..100).map(|i| (i * 3, i)).collect();
+    let mut seen = Vec::new();
+
+    storage.retain(|idx, v| {
+        seen.push(idx);
+        *v *= 10;
+        *v % 20 == 0
+    });
+
+    assert_eq!(seen, (0..100).map(|i| i * 3).collect::<Vec<_>>());
+    assert_eq!(storage.len(), 50);
+    assert!(storage.values().all(|v| v % 20 == 0));
+    assert_eq!(storage.get_checked(6), Some(&20));
+    assert_eq!(storage.get_checked(3), None);
+    storage.audit().unwrap();
+
+    // The removed components left their slots for new ones.
+    let capacity = storage.capacity();
+    storage.extend((0..50).map(|i| (1_000 + i, i)));
+    assert_eq!(storage.capacity(), capacity);
+}
+
+#[test]
+fn iter_follows_the_mask() {
+    let storage = strings([1, 4, 7, 300, 301]);
+    let has = mask([0, 1, 7, 300, 5_000]);
+
+    // Bits without a component are skipped.
+    let items: Vec<_> = storage.iter(&has).collect();
+    assert_eq!(
+        items,
+        vec![(1, &"1".into()), (7, &"7".into()), (300, &"300".into())]
+    );
+    assert_eq!(storage.iter(&BitSet::new()).count(), 0);
+}
+
+#[test]
+fn iter_mut_reaches_every_component() {
+    let mut storage = strings((0..200).step_by(3));
+    storage.take(9);
+    let has = storage.occupied_mask();
+
+    for (idx, v) in storage.iter_mut(&has) {
+        v.push_str(&format!("/{}", idx));
+    }
+
+    assert_eq!(storage.len(), 66);
+
+    for (idx, v) in storage.sorted_iter() {
+        assert_eq!(*v, format!("{}/{}", idx, idx));
+    }
+
+    // Only the masked components are touched.
+    for (_, v) in storage.iter_mut(&mask([0, 3])) {
+        v.clear();
+    }
+
+    assert_eq!(storage.values().filter(|v| v.is_empty()).count(), 2);
+}
+
+#[test]
+fn into_iter_yields_every_pair() {
+    let mut storage = strings([30, 2, 17, 4, 100]);
+    storage.take(17);
+
+    let mut pairs: Vec<_> = storage.into_iter().collect();
+    pairs.sort_by_key(|&(idx, _)| idx);
+    let expected = [2, 4, 30, 100].map(|i| (i, i.to_string()));
+    assert_eq!(pairs, expected);
+
+    let mut iter = strings(0..5).into_iter();
+    assert_eq!(iter.len(), 5);
+    iter.next();
+    assert_eq!(iter.len(), 4);
+}
+
+#[test]
+fn values_and_keys_match_the_inserted_set() {
+    let indices = [400, 3, 17, 0, 64, 65];
+    let mut storage: IdvStorage<u32> = indices.iter().map(|&i| (i, i * 2)).collect();
+    storage.take(17);
+    storage.replace(9, 18);
+
+    let keys: Vec<_> = storage.keys().collect();
+    assert_eq!(keys, [0, 3, 9, 64, 65, 400]);
+
+    let mut values: Vec<_> = storage.values().copied().collect();
+    values.sort_unstable();
+    assert_eq!(values, keys.iter().map(|i| i * 2).collect::<Vec<_>>());
+
+    for v in storage.values_mut() {
+        *v += 1;
+    }
+
+    assert_eq!(storage.values().count(), storage.len());
+    assert_eq!(storage.values().sum::<u32>(), 1082 + 6);
+    assert_eq!(storage.get_checked(400), Some(&801));
+
+    storage.clear();
+    assert_eq!(storage.keys().next(), None);
+    assert_eq!(storage.values().next(), None);
+}
+
+#[test]
+fn size_hints_and_fused_ends() {
+    let mut storage = strings((0..40).step_by(4));
+    storage.take(8);
+
+    let mut entries = storage.sorted_iter();
+
+    for remaining in (0..=9).rev() {
+        assert_eq!(entries.size_hint(), (remaining, Some(remaining)));
+        assert_eq!(entries.next().is_some(), remaining > 0);
+    }
+
+    assert!(entries.next().is_none());
+
+    // Masked iterators can only bound what is left by the storage length.
+    let has = mask(0..100);
+    let mut iter = storage.iter(&has);
+    assert_eq!(iter.size_hint(), (0, Some(9)));
+    assert_eq!(iter.by_ref().count(), 9);
+    assert!(iter.next().is_none() && iter.next().is_none());
+
+    let mut iter_mut = storage.iter_mut(&has);
+    assert_eq!(iter_mut.size_hint(), (0, Some(9)));
+    assert_eq!(iter_mut.by_ref().count(), 9);
+    assert!(iter_mut.next().is_none() && iter_mut.next().is_none());
+
+    let mut into_iter = storage.clone().into_iter();
+    assert_eq!(into_iter.size_hint(), (9, Some(9)));
+    assert_eq!(into_iter.by_ref().count(), 9);
+    assert_eq!(into_iter.size_hint(), (0, Some(0)));
+    assert!(into_iter.next().is_none() && into_iter.next().is_none());
+
+    let mut drain = storage.drain();
+    assert_eq!(drain.size_hint(), (9, Some(9)));
+    drain.next();
+    assert_eq!(drain.size_hint(), (8, Some(8)));
+    assert_eq!(drain.by_ref().count(), 8);
+    assert_eq!(drain.len(), 0);
+    assert!(drain.next().is_none() && drain.next().is_none());
+}
+
+#[test]
+fn rev_yields_descending_indices() {
+    let mut storage = strings((0..300).step_by(7));
+    storage.take(77);
+    let has = mask((0..400).filter(|i| i % 2 == 0));
+
+    let forward: Vec<_> = storage.iter(&has).map(|(idx, _)| idx).collect();
+    let mut backward: Vec<_> = storage.iter(&has).rev().map(|(idx, _)| idx).collect();
+    assert!(backward.windows(2).all(|w| w[0] > w[1]));
+    backward.reverse();
+    assert_eq!(backward, forward);
+
+    let backward_mut: Vec<_> = storage.iter_mut(&has).rev().map(|(idx, _)| idx).collect();
+    assert!(backward_mut.iter().rev().eq(&forward));
+
+    // Both ends meet without yielding anything twice.
+    let mut iter = storage.iter(&has);
+    let (first, last) = (iter.next().unwrap().0, iter.next_back().unwrap().0);
+    assert_eq!((first, last), (0, 294));
+    assert_eq!(iter.count(), forward.len() - 2);
+}
+
+#[test]
+fn drain_filter_fully_and_partially() {
+    let mut storage = strings(0..30);
+    let drained: Vec<_> = storage.drain_filter(|idx, _| idx % 3 == 0).collect();
+    assert_eq!(drained, strings((0..30).step_by(3)).into_vec());
+    assert_eq!(storage.len(), 20);
+    assert!(storage.keys().all(|idx| idx % 3 != 0));
+    storage.audit().unwrap();
+
+    // Dropping the iterator early still removes every match.
+    let mut drain = storage.drain_filter(|idx, v| {
+        v.push('!');
+        idx >= 20
+    });
+    assert_eq!(drain.next(), Some((20, "20!".into())));
+    drop(drain);
+
+    assert!(storage.keys().eq((1..20).filter(|i| i % 3 != 0)));
+    assert!(storage.values().all(|v| v.ends_with('!')));
+
+    // The freed slots are reused.
+    let capacity = storage.capacity();
+    storage.extend((100..117).map(|i| (i, i.to_string())));
+    assert_eq!(storage.capacity(), capacity);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn into_vec_is_sorted_by_index() {
+    let mut storage = IdvStorage::<u32>::default();
+
+    // Insert out of order and churn, so internal slot order differs from
+    // index order.
+    for i in [500, 7, 300, 1, 99, 42, 8] {
+        storage.replace(i, i * 10);
+    }
+
+    storage.take(300);
+    storage.replace(2, 20);
+
+    let pairs = storage.into_vec();
+    assert_eq!(
+        pairs,
+        [
+            (1, 10),
+            (2, 20),
+            (7, 70),
+            (8, 80),
+            (42, 420),
+            (99, 990),
+            (500, 5000)
+        ]
+    );
+    assert!(IdvStorage::<u32>::default().into_vec().is_empty());
+}
+
+#[test]
+fn internal_iter_mut_matches_internal_slot() {
+    let mut storage = strings((0..100).step_by(3));
+
+    for i in (0..100).step_by(9) {
+        storage.take(i);
+    }
+
+    storage.replace(500, "500".into());
+    let expected: Vec<_> = storage
+        .keys()
+        .map(|idx| (storage.internal_slot(idx).unwrap(), idx))
+        .collect();
+
+    let mut seen = Vec::new();
+
+    for (slot, v) in storage.internal_iter_mut() {
+        seen.push((slot, v.parse::<Index>().unwrap()));
+        v.push('*');
+    }
+
+    // Yielded in slot order, each slot holding the component of its index.
+    assert!(seen.windows(2).all(|w| w[0].0 < w[1].0));
+    seen.sort_by_key(|&(_, idx)| idx);
+    assert_eq!(seen, expected);
+    assert!(storage.values().all(|v| v.ends_with('*')));
+}
+
+#[test]
+fn shared_reference_yields_every_pair() {
+    let mut storage = strings([70, 1, 33, 8, 1_000, 2]);
+    storage.take(33);
+    storage.take(1_000);
+
+    let mut pairs = Vec::new();
+
+    for (idx, v) in &storage {
+        pairs.push((idx, v.clone()));
+    }
+
+    assert_eq!(pairs, storage.clone().into_vec());
+    assert_eq!(pairs.iter().map(|p| p.0).collect::<Vec<_>>(), [1, 2, 8, 70]);
+    assert_eq!((&storage).into_iter().len(), 4);
+    assert_eq!((&IdvStorage::<String>::default()).into_iter().next(), None);
+}
+
+#[test]
+fn sorted_iter_ignores_fragmentation() {
+    let wanted = [3, 9, 10, 11, 40, 41, 77, 300];
+
+    // Inserted in order into a fresh storage.
+    let packed = strings(wanted.iter().copied());
+
+    // Inserted backwards among others that are removed again, so the same
+    // indices end up in different, scattered slots.
+    let mut scattered = strings((0..400).rev());
+    scattered.retain(|idx, _| wanted.contains(&idx));
+
+    assert_ne!(
+        packed.internal_slot(300),
+        scattered.internal_slot(300),
+        "the layouts should differ"
+    );
+    assert!(scattered.fragmentation() > packed.fragmentation());
+    assert!(packed.sorted_iter().eq(scattered.sorted_iter()));
+    assert_eq!(
+        scattered.sorted_iter().map(|(i, _)| i).collect::<Vec<_>>(),
+        wanted
+    );
+}
+
+#[test]
+fn removing_while_walking_a_snapshot() {
+    let mut storage = strings(0..100);
+    let snapshot = storage.occupied_mask();
+
+    // Removing components, including ones the walk has not reached yet,
+    // leaves the snapshot untouched.
+    for idx in &snapshot {
+        if idx % 10 == 0 {
+            storage.take(idx + 5);
+            storage.take(idx);
+        }
+    }
+
+    assert_eq!(storage.len(), 80);
+    storage.audit().unwrap();
+
+    let removed = storage.remove_where(&snapshot, |idx, v| {
+        assert_eq!(*v, idx.to_string());
+        idx % 3 == 0
+    });
+
+    assert_eq!(removed, 27);
+    storage.audit().unwrap();
+    assert!(storage.keys().all(|i| i % 3 != 0 && i % 5 != 0));
+    assert_eq!(storage.remove_where(&snapshot, |_, _| false), 0);
+    assert_eq!(storage.remove_where(&snapshot, |_, _| true), 53);
+    assert!(storage.is_empty());
+}
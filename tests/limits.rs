@@ -0,0 +1,217 @@
+//! Behaviour at the edges of the index and slot ranges.
+
+use specs::hibitset::BitSetLike;
+use specs::world::Index;
+use specs_idvs::{CapacityError, IdvStorage, InsertError, MASK_INDEX_LIMIT, MAX_SPARSE_RATIO};
+
+// The limits are constants, so a world can check at compile time that its
+// entity count fits the storage it picked.
+const MAX_ENTITIES: usize = 50_000;
+const _: () = assert!(MAX_ENTITIES <= IdvStorage::<u32>::MAX_INTERNAL_SLOTS);
+const _: () = assert!(IdvStorage::<u32>::MAX_INTERNAL_SLOTS == u16::MAX as usize);
+const _: () = assert!(IdvStorage::<u32, 4, u32>::MAX_INTERNAL_SLOTS == u32::MAX as usize);
+const _: () = assert!(IdvStorage::<u32>::SPARSE_RATIO == 4);
+const _: () = assert!(IdvStorage::<u32, 16>::SPARSE_RATIO == 16);
+const _: () = assert!(IdvStorage::<u32, MAX_SPARSE_RATIO>::SPARSE_RATIO == MAX_SPARSE_RATIO);
+const _: () = assert!(IdvStorage::<u8, 2, u32>::MAX_INTERNAL_SLOTS > MASK_INDEX_LIMIT);
+
+#[test]
+fn occupied_mask_up_to_limit() {
+    let highest = (MASK_INDEX_LIMIT - 1) as Index;
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(3, 3);
+    storage.replace(highest, 7);
+
+    let mask = storage.occupied_mask();
+    assert_eq!(mask.iter().collect::<Vec<_>>(), vec![3, highest]);
+    assert_eq!(
+        storage.checked_occupied_mask().map(|m| m.iter().count()),
+        Some(2)
+    );
+}
+
+#[test]
+fn checked_occupied_mask_past_limit() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(3, 3);
+    storage.try_insert(20_000_000, 7).unwrap();
+    assert!(storage.checked_occupied_mask().is_none());
+
+    // Prefilled but empty redirects past the limit do not count.
+    storage.take(20_000_000);
+    storage.ensure_index(MASK_INDEX_LIMIT as Index);
+    let mask = storage.checked_occupied_mask().unwrap();
+    assert_eq!(mask.iter().collect::<Vec<_>>(), vec![3]);
+}
+
+#[test]
+#[should_panic(expected = "occupied index 16777216 does not fit in a BitSet")]
+#[cfg(target_pointer_width = "64")]
+fn occupied_mask_past_limit_panics() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(MASK_INDEX_LIMIT as Index, 1);
+    storage.occupied_mask();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn u32_slots_past_bitset_limit() {
+    let count = MASK_INDEX_LIMIT + 10;
+    let mut storage = IdvStorage::<u8, 4, u32>::with_capacity(20_000_000);
+    assert!(storage.capacity() >= 20_000_000);
+
+    for i in 0..count as Index {
+        storage.replace(i, i as u8);
+    }
+
+    assert_eq!(storage.len(), count);
+    assert_eq!(
+        storage.get_checked(count as Index - 1),
+        Some(&((count - 1) as u8))
+    );
+    assert_eq!(storage.internal_slot(count as Index - 1), Some(count - 1));
+    assert_eq!(storage.take(MASK_INDEX_LIMIT as Index + 3), Some(3));
+    assert!(!storage.contains(MASK_INDEX_LIMIT as Index + 3));
+}
+
+#[test]
+fn lookups_near_u32_max() {
+    let mut storage: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+
+    for idx in [Index::MAX - 1, Index::MAX] {
+        assert!(!storage.contains(idx));
+        assert_eq!(storage.get_checked(idx), None);
+        assert_eq!(storage.get_checked_mut(idx), None);
+        assert_eq!(storage.internal_slot(idx), None);
+        assert_eq!(storage.take(idx), None);
+        assert!(!storage.move_index(idx, 3));
+        storage.mark_remove(idx);
+    }
+
+    storage.swap(Index::MAX - 1, Index::MAX);
+    assert_eq!(storage.get_array([Index::MAX, 2]), [None, Some(&2)]);
+    assert_eq!(storage.count_in_range(Index::MAX - 10..Index::MAX), 0);
+    assert_eq!(storage.count_in_range(5..Index::MAX), 5);
+    assert!(storage.contains_range(Index::MAX..Index::MAX));
+    assert!(!storage.contains_range(Index::MAX - 1..Index::MAX));
+    assert_eq!(storage.iter_range(8..Index::MAX).count(), 2);
+
+    storage.truncate(Index::MAX as usize);
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 10);
+}
+
+#[test]
+fn paged_storage_near_u32_max() {
+    let mut single = specs_idvs::PagedIdvStorage::<u32>::default();
+    single.replace(Index::MAX, 2);
+    let page_bytes = single.memory_usage().total_bytes();
+    // One page of redirects and a batch of slots, not a table up to the index.
+    assert!(page_bytes < specs_idvs::PAGE_LEN * 8, "{}", page_bytes);
+
+    let mut storage = specs_idvs::PagedIdvStorage::<u32>::default();
+    storage.replace(Index::MAX - 1, 1);
+    storage.replace(Index::MAX, 2);
+    storage.replace(0, 3);
+
+    assert_eq!(storage.get_checked(Index::MAX - 1), Some(&1));
+    assert_eq!(storage.get_checked(Index::MAX), Some(&2));
+    assert_eq!(storage.page_count(), 2);
+    assert_eq!(storage.memory_usage().occupied_slots, 3);
+    assert!(storage.memory_usage().total_bytes() <= 2 * page_bytes);
+    assert_eq!(storage.take(Index::MAX - 1), Some(1));
+    assert_eq!(storage.take(Index::MAX), Some(2));
+    assert_eq!(storage.page_count(), 1);
+    assert!(storage.memory_usage().total_bytes() < page_bytes);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn around_bitset_limit() {
+    let limit = MASK_INDEX_LIMIT as Index;
+    let mut storage = IdvStorage::<u32, 4, u32>::with_capacity(MASK_INDEX_LIMIT + 2);
+
+    for idx in [limit - 1, limit, limit + 1] {
+        storage.replace(idx, idx);
+    }
+
+    assert_eq!(
+        storage.keys().collect::<Vec<_>>(),
+        vec![limit - 1, limit, limit + 1]
+    );
+    assert_eq!(storage.get_checked(limit), Some(&limit));
+    assert!(storage.checked_occupied_mask().is_none());
+
+    storage.take(limit + 1);
+    storage.take(limit);
+    let mask = storage.occupied_mask();
+    assert_eq!(mask.iter().collect::<Vec<_>>(), vec![limit - 1]);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn exhausted_slots_hand_value_back() {
+    let mut storage = IdvStorage::<String>::default();
+
+    for i in 0..u16::MAX as Index {
+        storage.try_insert(i * 2, i.to_string()).unwrap();
+    }
+
+    let usage = storage.memory_usage();
+    assert_eq!(storage.len(), IdvStorage::<String>::MAX_INTERNAL_SLOTS);
+
+    let err = storage.try_insert(1, "one".into()).unwrap_err();
+    assert_eq!(err, CapacityError("one".into()));
+    assert_eq!(err.to_string(), "storage has no free internal slots left");
+    assert!(!storage.contains(1));
+    assert_eq!(storage.memory_usage(), usage);
+
+    // Overwriting needs no new slot, and a removal frees one again.
+    storage.try_insert(0, "zero".into()).unwrap();
+    storage.take(2);
+    storage.try_insert(1, "one".into()).unwrap();
+    assert_eq!(storage.get_checked(1).map(String::as_str), Some("one"));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn both_redirect_widths() {
+    // Indices are not limited by the redirect width, only the component count.
+    let mut narrow = IdvStorage::<u32>::default();
+
+    for i in 0..1_000 {
+        narrow.replace(100_000 + i * 97, i);
+    }
+
+    assert_eq!(narrow.get_checked(100_000 + 999 * 97), Some(&999));
+    assert_eq!(narrow.len(), 1_000);
+
+    let mut wide = IdvStorage::<u32, 4, u32>::default();
+
+    for i in 0..70_000 {
+        wide.try_insert(i, i).unwrap();
+    }
+
+    assert_eq!(wide.len(), 70_000);
+    assert_eq!(wide.internal_slot(69_999), Some(69_999));
+    assert!((0..70_000).all(|i| wide.get_checked(i) == Some(&i)));
+    assert_eq!(wide.take(65_536), Some(65_536));
+    wide.audit().unwrap();
+    narrow.audit().unwrap();
+}
+
+#[test]
+fn checked_insert_on_full_storage() {
+    let mut storage: IdvStorage<u32> = (0..u16::MAX as Index).map(|i| (i, i)).collect();
+
+    assert_eq!(storage.checked_insert(7, 70), Ok(Some(7)));
+    let err = storage.checked_insert(100_000, 1).unwrap_err();
+    assert_eq!(err, InsertError::Capacity(1));
+    assert_eq!(err.into_inner(), 1);
+    assert!(!storage.contains(100_000));
+
+    storage.take(0);
+    assert_eq!(storage.checked_insert(100_000, 1), Ok(None));
+    assert_eq!(storage.checked_insert(100_000, 2), Ok(Some(1)));
+    storage.audit().unwrap();
+}
@@ -0,0 +1,68 @@
+//! Fragmentation warnings, with the `log` feature.
+
+#![cfg(feature = "log")]
+
+use log::{Level, Log, Metadata, Record};
+use specs_idvs::IdvStorage;
+use std::sync::Mutex;
+
+/// Keeps every warning logged while the tests run.
+struct Capture(Mutex<Vec<String>>);
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+fn warnings() -> Vec<String> {
+    CAPTURE.0.lock().unwrap().clone()
+}
+
+#[test]
+fn warns_once_past_the_limit() {
+    log::set_logger(&CAPTURE).unwrap();
+    log::set_max_level(log::LevelFilter::Warn);
+
+    let mut storage: IdvStorage<u32> = (0..1_000).map(|i| (i, i)).collect();
+    storage.warn_on_fragmentation(0.5);
+
+    // The highest slot stays occupied, so each removal adds a thousandth.
+    // The last check before the limit is at 448 removals.
+    for i in 0..505 {
+        storage.take(i);
+    }
+
+    assert!(storage.fragmentation() > 0.5);
+    assert!(warnings().is_empty());
+
+    // Checked again at 512.
+    for i in 505..512 {
+        storage.take(i);
+    }
+
+    let logged = warnings();
+    assert_eq!(logged.len(), 1);
+    assert!(
+        logged[0].starts_with("storage fragmentation 0.51 exceeds 0.50 with 488 components"),
+        "{}",
+        logged[0]
+    );
+
+    // Not repeated while fragmentation stays past the limit.
+    for i in 512..700 {
+        storage.take(i);
+    }
+
+    assert_eq!(warnings().len(), 1);
+}
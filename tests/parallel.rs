@@ -0,0 +1,71 @@
+//! Parallel access to a storage on the rayon thread pool, with the
+//! `parallel` feature.
+
+#![cfg(feature = "parallel")]
+
+use specs::prelude::*;
+use specs_idvs::IdvStorage;
+
+#[derive(Debug, PartialEq)]
+struct Mass(u64);
+
+impl Component for Mass {
+    type Storage = IdvStorage<Self>;
+}
+
+#[test]
+fn par_join_matches_serial_join() {
+    let mut world = World::new();
+    world.register::<Mass>();
+
+    for i in 0..20_000u64 {
+        let builder = world.create_entity();
+
+        if i % 7 != 3 {
+            builder.with(Mass(i * i)).build();
+        } else {
+            builder.build();
+        }
+    }
+
+    let mut storage = world.write_storage::<Mass>();
+    let serial: u64 = (&storage).join().map(|m| m.0).sum();
+    let parallel: u64 = (&storage).par_join().map(|m| m.0).sum();
+    assert_eq!(parallel, serial);
+
+    (&mut storage).par_join().for_each(|m| m.0 += 1);
+    let bumped: u64 = (&storage).join().map(|m| m.0).sum();
+    assert_eq!(bumped, serial + storage.count() as u64);
+}
+
+#[test]
+fn par_iter_mut_matches_serial_iter_mut() {
+    let mut parallel: IdvStorage<u64> = (0..2_000)
+        .filter(|i| i % 5 != 2)
+        .map(|i| (i, u64::from(i)))
+        .collect();
+    let mut serial = parallel.clone();
+
+    // Vacant and out of range bits are skipped.
+    let mut mask = BitSet::new();
+
+    for i in (0..3_000).filter(|i| i % 3 != 0) {
+        mask.add(i);
+    }
+
+    parallel
+        .par_iter_mut(&mask)
+        .for_each(|(idx, v)| *v = *v * 3 + u64::from(idx));
+
+    for (idx, v) in serial.iter_mut(&mask) {
+        *v = *v * 3 + u64::from(idx);
+    }
+
+    assert_eq!(parallel, serial);
+    assert_eq!(parallel.get_checked(4), Some(&16));
+    assert_eq!(parallel.get_checked(3), Some(&3));
+    assert_eq!(
+        parallel.par_iter_mut(&mask).count(),
+        serial.iter(&mask).count()
+    );
+}
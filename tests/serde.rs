@@ -0,0 +1,175 @@
+//! Serde round trips, and deserialization of malformed or hostile input.
+
+#![cfg(feature = "serde")]
+
+use specs_idvs::{IdvStorage, MASK_INDEX_LIMIT};
+
+type Storage = IdvStorage<u32>;
+
+fn from_json(json: &str) -> Result<Storage, String> {
+    serde_json::from_str(json).map_err(|err| err.to_string())
+}
+
+#[test]
+fn round_trip() {
+    let mut storage: Storage = (0..50).map(|i| (i * 7, i)).collect();
+
+    for i in (0..50).step_by(3) {
+        storage.take(i * 7);
+    }
+
+    let json = serde_json::to_string(&storage).unwrap();
+    let back = from_json(&json).unwrap();
+    back.audit().unwrap();
+    assert_eq!(back, storage);
+}
+
+#[test]
+fn rejects_index_past_limit() {
+    // Accepting this would allocate gigabytes of redirect groups.
+    let err = from_json("[[4294967294, 1]]").unwrap_err();
+    assert!(
+        err.contains("index 4294967294 is out of range, the limit is"),
+        "{}",
+        err
+    );
+
+    let highest = MASK_INDEX_LIMIT - 1;
+    let storage = from_json(&format!("[[{}, 1]]", highest)).unwrap();
+    assert_eq!(storage.get_checked(highest as u32), Some(&1));
+    assert!(from_json(&format!("[[{}, 1]]", MASK_INDEX_LIMIT)).is_err());
+}
+
+#[test]
+fn custom_index_limit() {
+    let mut de = serde_json::Deserializer::from_str("[[3, 1], [99, 2]]");
+    let storage = Storage::deserialize_bounded(&mut de, 100).unwrap();
+    assert_eq!(storage.len(), 2);
+
+    let mut de = serde_json::Deserializer::from_str("[[3, 1], [100, 2]]");
+    let err = Storage::deserialize_bounded(&mut de, 100).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("index 100 is out of range, the limit is 100"));
+}
+
+#[test]
+fn rejects_repeated_index() {
+    let err = from_json("[[3, 1], [4, 2], [3, 5]]").unwrap_err();
+    assert!(err.contains("index 3 appears more than once"), "{}", err);
+}
+
+#[test]
+fn rejects_malformed_pairs() {
+    for json in [
+        "[[3]]",
+        "[[3, 1, 2]]",
+        "[[-1, 1]]",
+        "[[4294967296, 1]]",
+        "[[\"3\", 1]]",
+        "[[3, \"one\"]]",
+        "{\"3\": 1}",
+        "[[3, 1]",
+    ] {
+        assert!(from_json(json).is_err(), "accepted {}", json);
+    }
+}
+
+#[test]
+fn rejects_too_many_components() {
+    let pairs: Vec<_> = (0..=Storage::MAX_INTERNAL_SLOTS as u32)
+        .map(|i| (i, i))
+        .collect();
+    let json = serde_json::to_string(&pairs).unwrap();
+    let err = from_json(&json).unwrap_err();
+    assert!(err.contains("no free internal slots"), "{}", err);
+}
+
+fn compact_json(storage: &Storage) -> String {
+    let mut out = Vec::new();
+    storage
+        .serialize_compact(&mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn from_compact_json(json: &str) -> Result<Storage, String> {
+    let mut de = serde_json::Deserializer::from_str(json);
+    Storage::deserialize_compact(&mut de).map_err(|err| err.to_string())
+}
+
+#[test]
+fn compact_round_trip() {
+    let mut storage: Storage = (0..50).map(|i| (1_000_000 + i * 7, i)).collect();
+    storage.replace(0, 99);
+    storage.take(1_000_014);
+
+    let json = compact_json(&storage);
+    assert!(
+        json.starts_with("[[0,99],[999999,0],[6,1],[13,3]"),
+        "{}",
+        json
+    );
+
+    let back = from_compact_json(&json).unwrap();
+    back.audit().unwrap();
+    assert_eq!(back, storage);
+    assert!(json.len() < serde_json::to_string(&storage).unwrap().len());
+    assert_eq!(from_compact_json("[]").unwrap().len(), 0);
+}
+
+#[test]
+fn size_scales_with_len() {
+    let small: Storage = (0..10).map(|i| (i, i)).collect();
+
+    // The same ten components in a storage with far more capacity, free
+    // slots and redirect groups.
+    let mut large = Storage::with_capacity(10_000);
+
+    for i in 0..5_000 {
+        large.replace(i, i);
+    }
+
+    large.retain(|idx, _| idx < 10);
+    large.reserve(20_000);
+    assert_eq!(large, small);
+
+    let formats = [serde_json::to_string::<Storage>, |s: &Storage| {
+        Ok(compact_json(s))
+    }];
+
+    for to_string in formats {
+        assert_eq!(
+            to_string(&large).unwrap().len(),
+            to_string(&small).unwrap().len()
+        );
+    }
+
+    let doubled: Storage = (0..20).map(|i| (i, i)).collect();
+    let (ten, twenty) = (compact_json(&small).len(), compact_json(&doubled).len());
+    assert!(
+        twenty > ten * 2 - 10 && twenty < ten * 3,
+        "{} {}",
+        ten,
+        twenty
+    );
+}
+
+#[test]
+fn compact_rejects_bad_gaps() {
+    let highest = MASK_INDEX_LIMIT as u32 - 1;
+    assert!(from_compact_json(&format!("[[{}, 1]]", highest)).is_ok());
+
+    let err = from_compact_json(&format!("[[{}, 1], [0, 2]]", highest)).unwrap_err();
+    assert!(err.contains("is out of range, the limit is"), "{}", err);
+
+    let err = from_compact_json("[[5, 1], [4294967295, 2]]").unwrap_err();
+    assert!(
+        err.contains("gap 4294967295 after index 5 overflows"),
+        "{}",
+        err
+    );
+
+    assert!(from_compact_json("[[3]]").is_err());
+    assert!(from_compact_json("[[-1, 1]]").is_err());
+}
@@ -0,0 +1,195 @@
+//! Exercises the uninitialized data slots through the public API. Every path
+//! that moves a component in or out of a slot is covered here, so running
+//! these under `cargo miri test` checks the unsafe code behind them.
+
+use specs::storage::UnprotectedStorage;
+use specs_idvs::IdvStorage;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Records its id in the shared log when dropped.
+struct Tracked {
+    id: u32,
+    drops: Rc<RefCell<Vec<u32>>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.borrow_mut().push(self.id);
+    }
+}
+
+fn tracked(n: u32, drops: &Rc<RefCell<Vec<u32>>>) -> IdvStorage<Tracked> {
+    (0..n)
+        .map(|id| {
+            (
+                id * 2,
+                Tracked {
+                    id,
+                    drops: drops.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Asserts that exactly the ids in `range` were dropped, each once.
+fn assert_dropped_once(drops: &Rc<RefCell<Vec<u32>>>, range: std::ops::Range<u32>) {
+    let mut dropped = drops.borrow().clone();
+    dropped.sort_unstable();
+    assert_eq!(dropped, range.collect::<Vec<_>>());
+}
+
+fn strings(n: u32) -> IdvStorage<String> {
+    (0..n).map(|i| (i * 3, i.to_string())).collect()
+}
+
+#[test]
+fn insert_overwrite_take() {
+    let mut storage = strings(20);
+    assert_eq!(storage.replace(3, "three".into()), Some("1".into()));
+    assert_eq!(storage.take(6), Some("2".into()));
+    assert_eq!(storage.take(6), None);
+    assert_eq!(storage.get_checked(3).map(String::as_str), Some("three"));
+    assert_eq!(storage.get_checked(4), None);
+
+    unsafe {
+        storage.insert(6, "six".into());
+        assert_eq!(storage.get(6), "six");
+        storage.get_mut(6).push('!');
+        assert_eq!(storage.remove(6), "six!");
+    }
+
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 19);
+}
+
+#[test]
+fn reuse_vacated_slots() {
+    let mut storage = strings(40);
+
+    for i in (0..40).step_by(2) {
+        storage.take(i * 3);
+    }
+
+    for i in 0..20 {
+        storage.replace(1000 + i, format!("new {}", i));
+    }
+
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 40);
+    assert_eq!(storage.get_checked(1005).map(String::as_str), Some("new 5"));
+    assert_eq!(storage.get_checked(3).map(String::as_str), Some("1"));
+}
+
+#[test]
+fn clear_truncate_and_shrink() {
+    let mut storage = strings(30);
+    storage.truncate(45);
+    assert_eq!(storage.len(), 15);
+    assert_eq!(storage.get_checked(45), None);
+
+    for i in 0..10 {
+        storage.take(i * 3);
+    }
+
+    storage.compact();
+    storage.shrink_to_fit();
+    storage.audit().unwrap();
+    assert_eq!(storage.values().count(), 5);
+
+    storage.clear();
+    assert!(storage.is_empty());
+    storage.replace(1, "again".into());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn clone_and_clone_from() {
+    let source = strings(25);
+    let copy = source.clone();
+    assert_eq!(copy, source);
+
+    let mut target = strings(40);
+
+    for i in (0..40).step_by(3) {
+        target.take(i * 3);
+    }
+
+    target.clone_from(&source);
+    target.audit().unwrap();
+    assert_eq!(target, source);
+
+    let mut small = strings(2);
+    small.clone_from(&target);
+    assert_eq!(small, source);
+}
+
+#[test]
+fn moves_without_clone() {
+    let mut storage = strings(10);
+    storage.sort_by_index();
+    assert!(storage.move_index(0, 100));
+    storage.swap(3, 100);
+    assert_eq!(storage.get_checked(3).map(String::as_str), Some("0"));
+    assert_eq!(storage.get_checked(100).map(String::as_str), Some("1"));
+
+    let drained: Vec<_> = storage.drain().collect();
+    assert_eq!(drained.len(), 10);
+    assert!(storage.is_empty());
+}
+
+#[test]
+#[cfg(not(feature = "cache-aligned"))]
+fn slots_have_no_discriminant() {
+    use std::mem;
+
+    /// No niche, so `Option` around it needs extra room.
+    #[derive(Debug, PartialEq)]
+    struct NoNiche([u32; 3]);
+
+    assert!(mem::size_of::<Option<NoNiche>>() > mem::size_of::<NoNiche>());
+
+    let mut storage = IdvStorage::<NoNiche>::with_capacity(64);
+
+    for i in 0..64 {
+        storage.replace(i, NoNiche([i; 3]));
+    }
+
+    let usage = storage.memory_usage();
+    assert_eq!(
+        usage.data_bytes / storage.capacity(),
+        mem::size_of::<NoNiche>()
+    );
+    assert_eq!(storage[10], NoNiche([10; 3]));
+}
+
+#[test]
+fn drops_each_value_once() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+
+    // Removing some values first leaves vacant slots in between.
+    let mut storage = tracked(30, &drops);
+    drop(storage.take(4));
+    storage.mark_remove(6);
+    assert_dropped_once(&drops, 2..3);
+    drop(storage);
+    assert_dropped_once(&drops, 0..30);
+
+    drops.borrow_mut().clear();
+    let mut storage = tracked(30, &drops);
+    storage.clear();
+    assert_dropped_once(&drops, 0..30);
+    storage.clear();
+    drop(storage);
+    assert_dropped_once(&drops, 0..30);
+
+    drops.borrow_mut().clear();
+    let mut storage = tracked(30, &drops);
+    storage.truncate(20);
+    assert_dropped_once(&drops, 10..30);
+    storage.truncate(0);
+    assert_dropped_once(&drops, 0..30);
+    drop(storage);
+    assert_dropped_once(&drops, 0..30);
+}
@@ -0,0 +1,47 @@
+//! Operation counters, with the `stats` feature.
+
+#![cfg(feature = "stats")]
+
+use specs::hibitset::BitSet;
+use specs::storage::UnprotectedStorage;
+use specs_idvs::{IdvStorage, OpStats};
+
+#[test]
+fn counters_match_a_known_sequence() {
+    let mut storage = IdvStorage::<u32>::default();
+    assert_eq!(storage.op_stats(), OpStats::default());
+
+    // Two grows: the first eight slots, then eight more.
+    for i in 0..12 {
+        storage.replace(i, i);
+    }
+
+    storage.replace(3, 30);
+    storage.replace(4, 40);
+    storage.take(5);
+    storage.take(5);
+
+    let mut has = BitSet::new();
+    has.add(0);
+    has.add(1);
+    has.add(100);
+    unsafe { storage.clean(&has) };
+
+    let expected = OpStats {
+        inserts: 12,
+        overwrites: 2,
+        removes: 3,
+        cleans: 1,
+        grows: 2,
+    };
+    assert_eq!(storage.op_stats(), expected);
+
+    // Clearing counts a removal per component.
+    storage.clear();
+    assert_eq!(storage.op_stats().removes, 3 + 9);
+
+    storage.reset_op_stats();
+    assert_eq!(storage.op_stats(), OpStats::default());
+    storage.replace(0, 0);
+    assert_eq!(storage.op_stats().inserts, 1);
+}
@@ -0,0 +1,1485 @@
+//! Behaviour of the core `IdvStorage` operations, checked through the
+//! public API and `audit`.
+
+use specs::hibitset::BitSet;
+use specs::storage::UnprotectedStorage;
+use specs::world::Index;
+use specs_idvs::{CleanCursor, CleanProgress, IdvStorage, SlotPolicy};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+fn mask(indices: impl IntoIterator<Item = Index>) -> BitSet {
+    let mut mask = BitSet::new();
+
+    for idx in indices {
+        mask.add(idx);
+    }
+
+    mask
+}
+
+#[test]
+fn swap_combinations() {
+    let mut storage: IdvStorage<u32> = vec![(1, 10), (2, 20)].into_iter().collect();
+
+    storage.swap(1, 2);
+    assert_eq!(storage.get_checked(1), Some(&20));
+    assert_eq!(storage.get_checked(2), Some(&10));
+
+    storage.swap(2, 9);
+    assert_eq!(storage.get_checked(2), None);
+    assert_eq!(storage.get_checked(9), Some(&10));
+
+    storage.swap(5, 6);
+    assert_eq!(storage.get_checked(5), None);
+    assert_eq!(storage.get_checked(6), None);
+
+    storage.swap(1, 1);
+    assert_eq!(storage.get_checked(1), Some(&20));
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 2);
+}
+
+#[test]
+fn swap_down_trims_groups() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(100, 1);
+    storage.swap(100, 0);
+    assert_eq!(storage.group_stats(), (1, 1));
+
+    storage.take(0);
+    assert_eq!(storage.group_stats(), (0, 0));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn move_index_to_vacant_and_occupied() {
+    let mut storage: IdvStorage<String> = (0..4).map(|i| (i, i.to_string())).collect();
+
+    assert!(storage.move_index(1, 10));
+    assert_eq!(storage.get_checked(1), None);
+    assert_eq!(storage.get_checked(10).map(String::as_str), Some("1"));
+
+    // The component already at the destination is dropped.
+    assert!(storage.move_index(10, 2));
+    assert_eq!(storage.get_checked(2).map(String::as_str), Some("1"));
+    assert_eq!(storage.len(), 3);
+
+    assert!(!storage.move_index(7, 0));
+    assert_eq!(storage.get_checked(0).map(String::as_str), Some("0"));
+    assert!(storage.move_index(0, 0));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn move_index_down_trims_groups() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(3, 1);
+    assert!(storage.move_index(3, 50));
+    assert!(storage.move_index(50, 1));
+    assert_eq!(storage.group_stats(), (1, 1));
+    assert_eq!(storage.get_checked(1), Some(&1));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn clean_chunked_matches_clean() {
+    let full: IdvStorage<u32> = (0..200).map(|i| (i, i)).collect();
+    let has = mask((0..300).filter(|i| i % 3 != 0));
+
+    let mut expected = full.clone();
+    unsafe { expected.clean(&has) };
+
+    let mut chunked = full;
+    let mut cursor = CleanCursor::new();
+    let mut calls = 1;
+
+    while chunked.clean_chunked(&has, &mut cursor, 16) == CleanProgress::Pending {
+        calls += 1;
+        chunked.audit().unwrap();
+    }
+
+    assert!(calls > 5);
+    assert_eq!(chunked, expected);
+    assert_eq!(cursor.cleaned(), 133);
+    chunked.audit().unwrap();
+}
+
+#[test]
+fn clean_chunked_budget_counts_mask_bits() {
+    let mut storage: IdvStorage<u32> = (0..100).step_by(2).map(|i| (i, i)).collect();
+    let has = mask((40..60).chain(500..510));
+    let mut cursor = CleanCursor::new();
+
+    // Vacant indices of the mask use up the budget like occupied ones, and
+    // a zero budget still handles one index per call.
+    assert!(!storage.clean_chunked(&has, &mut cursor, 4).is_done());
+    assert_eq!(cursor.cleaned(), 2);
+    let mut calls = 1;
+
+    while !storage.clean_chunked(&has, &mut cursor, 0).is_done() {
+        calls += 1;
+    }
+
+    assert_eq!(calls, 16);
+    assert_eq!(cursor.cleaned(), 10);
+    assert_eq!(storage.len(), 40);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn clean_chunked_fresh_cursor_after_abandoned_pass() {
+    let mut storage: IdvStorage<u32> = (0..100).map(|i| (i, i)).collect();
+    let mut abandoned = CleanCursor::new();
+    assert!(!storage
+        .clean_chunked(&mask(50..100), &mut abandoned, 10)
+        .is_done());
+
+    // An abandoned pass leaves no state in the storage, a new pass with
+    // another mask still reaches its lowest indices.
+    let mut cursor = CleanCursor::new();
+    assert!(storage
+        .clean_chunked(&mask(0..5), &mut cursor, 10)
+        .is_done());
+    assert_eq!(cursor.cleaned(), 5);
+    assert_eq!(storage.keys().next(), Some(5));
+    assert_eq!(storage.len(), 85);
+
+    // A finished cursor starts the next pass over and resets its count.
+    assert!(storage
+        .clean_chunked(&mask(5..8), &mut cursor, 10)
+        .is_done());
+    assert_eq!(cursor.cleaned(), 3);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn removed_slots_are_reused() {
+    const N: Index = 1_000;
+
+    let mut storage = IdvStorage::<u32>::default();
+
+    for i in 0..N {
+        storage.replace(i * 3, i);
+    }
+
+    let capacity = storage.capacity();
+    let data_bytes = storage.memory_usage().data_bytes;
+    let mut usage = None;
+
+    for _ in 0..3 {
+        for i in 0..N {
+            assert_eq!(storage.take(i * 3), Some(i));
+        }
+
+        assert!(storage.is_empty());
+
+        for i in 0..N {
+            storage.replace(i * 3, i);
+        }
+
+        assert_eq!(storage.capacity(), capacity);
+        assert_eq!(storage.memory_usage().data_bytes, data_bytes);
+
+        // Only the free list grows, once, to hold every slot.
+        let usage = usage.get_or_insert(storage.memory_usage());
+        assert_eq!(storage.memory_usage(), *usage);
+        storage.audit().unwrap();
+    }
+}
+
+#[test]
+fn clean_removes_exactly_the_mask() {
+    let indices = [0, 1, 2, 3, 5, 6, 7, 9, 13, 20, 21, 22, 23, 40];
+    let cleaned = [0, 2, 6, 7, 13, 23];
+    let mut storage: IdvStorage<u32> = indices.iter().map(|&i| (i, i * 10)).collect();
+
+    // Bits past the stored indices are ignored.
+    unsafe { storage.clean(mask(cleaned.iter().copied().chain([41, 500]))) };
+
+    for i in 0..=40 {
+        let kept = indices.contains(&i) && !cleaned.contains(&i);
+        let expected = kept.then(|| i * 10);
+        assert_eq!(storage.get_checked(i).copied(), expected, "{}", i);
+    }
+
+    assert_eq!(storage.len(), indices.len() - cleaned.len());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn insert_over_occupied_reuses_slot() {
+    let mut storage: IdvStorage<String> = (0..10).map(|i| (i, i.to_string())).collect();
+    let usage = storage.memory_usage();
+    let slot = storage.internal_slot(4);
+
+    unsafe { storage.insert(4, "four".into()) };
+    assert_eq!(storage.replace(4, "vier".into()), Some("four".into()));
+
+    assert_eq!(storage.memory_usage(), usage);
+    assert_eq!(storage.internal_slot(4), slot);
+    assert_eq!(storage.get_checked(4).map(String::as_str), Some("vier"));
+    assert_eq!(storage.len(), 10);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn vacant_indices_do_not_alias_slot_zero() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(0, 7);
+    storage.replace(9, 8);
+    assert_eq!(storage.internal_slot(0), Some(0));
+
+    // Indices 1 to 3 share the group of index 0 and 8 and 10 that of 9.
+    for i in [1, 2, 3, 8, 10, 11] {
+        assert!(!storage.contains(i));
+        assert_eq!(storage.get_checked(i), None);
+        assert_eq!(storage.internal_slot(i), None);
+    }
+
+    assert_eq!(storage.take(1), None);
+    assert_eq!(storage.get_checked(0), Some(&7));
+}
+
+#[test]
+fn many_inserts_with_empty_free_list() {
+    let grows = Arc::new(AtomicUsize::new(0));
+    let mut storage = IdvStorage::<u32>::builder().growth(1).build();
+    let counter = grows.clone();
+    storage.on_grow(move |_| {
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+
+    // Whenever the free list runs dry the next insert grows the data slots,
+    // up to the very last one a `u16` can address.
+    for i in 0..u16::MAX as Index {
+        storage.replace(i, i);
+    }
+
+    assert!(grows.load(Ordering::Relaxed) > 10);
+    assert_eq!(storage.len(), u16::MAX as usize);
+    assert_eq!(storage.memory_usage().free_slots, 0);
+    assert!((0..u16::MAX as Index).all(|i| storage.get_checked(i) == Some(&i)));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn redirect_groups_match_highest_index() {
+    for (idx, groups) in [(0, 1), (3, 1), (4, 2), (1_000, 251), (99_999, 25_000)] {
+        let mut storage = IdvStorage::<u32>::default();
+        storage.replace(idx, 1);
+        assert_eq!(storage.group_stats(), (groups, 1), "index {}", idx);
+    }
+
+    let mut wide = IdvStorage::<u32, 16>::default();
+    wide.replace(99_999, 1);
+    assert_eq!(wide.group_stats(), (6_250, 1));
+}
+
+fn exercise_ratio<const R: usize>() {
+    let mut storage = IdvStorage::<u32, R>::default();
+
+    for i in (0..100).step_by(3) {
+        storage.replace(i, i);
+    }
+
+    assert_eq!(storage.group_stats().0, 99 / R + 1);
+    assert_eq!(storage.take(9), Some(9));
+    unsafe { storage.clean(mask([0, 3, 6])) };
+    assert_eq!(storage.len(), 30);
+
+    for i in 0..100 {
+        let expected = (i % 3 == 0 && i > 9).then_some(i);
+        assert_eq!(storage.get_checked(i).copied(), expected, "ratio {}", R);
+    }
+
+    storage.audit().unwrap();
+}
+
+#[test]
+fn sparse_ratios() {
+    exercise_ratio::<1>();
+    exercise_ratio::<2>();
+    exercise_ratio::<4>();
+    exercise_ratio::<8>();
+}
+
+#[test]
+fn clone_is_independent() {
+    let mut original: IdvStorage<String> = (0..20).map(|i| (i * 2, i.to_string())).collect();
+    original.take(4);
+    let copy = original.clone();
+    assert_eq!(copy, original);
+    assert_eq!(copy.dump_layout(), original.dump_layout());
+
+    original.get_checked_mut(0).unwrap().push('!');
+    original.replace(1, "new".into());
+    original.take(6);
+
+    assert_eq!(copy.get_checked(0).map(String::as_str), Some("0"));
+    assert_eq!(copy.get_checked(1), None);
+    assert_eq!(copy.get_checked(6).map(String::as_str), Some("3"));
+    assert_eq!(copy.len(), 19);
+    copy.audit().unwrap();
+}
+
+#[test]
+fn len_tracks_every_operation() {
+    let mut storage = IdvStorage::<u32>::default();
+    assert!(storage.is_empty());
+
+    for i in 0..10 {
+        unsafe { storage.insert(i, i) };
+    }
+
+    assert_eq!(storage.len(), 10);
+
+    // Overwrites keep the count.
+    unsafe { storage.insert(3, 30) };
+    storage.replace(4, 40);
+    assert_eq!(storage.len(), 10);
+
+    assert_eq!(unsafe { storage.remove(5) }, 5);
+    assert_eq!(storage.take(5), None);
+    assert_eq!(storage.len(), 9);
+
+    // Bits for vacant indices do not count.
+    unsafe { storage.clean(mask([0, 1, 5, 50])) };
+    assert_eq!(storage.len(), 7);
+    assert!(!storage.is_empty());
+
+    unsafe { storage.clean(mask(0..10)) };
+    assert_eq!(storage.len(), 0);
+    assert!(storage.is_empty());
+}
+
+#[test]
+fn contains_present_absent_and_out_of_range() {
+    let mut storage: IdvStorage<u32> = vec![(0, 1), (5, 2)].into_iter().collect();
+
+    assert!(storage.contains(0));
+    assert!(storage.contains(5));
+    assert!(!storage.contains(4));
+    assert!(!storage.contains(6));
+    assert!(!storage.contains(8));
+    assert!(!storage.contains(1_000_000));
+    assert!(!storage.contains(Index::MAX));
+
+    storage.take(5);
+    assert!(!storage.contains(5));
+    assert!(!IdvStorage::<u32>::default().contains(0));
+}
+
+#[test]
+fn checked_access() {
+    let mut storage: IdvStorage<u32> = vec![(0, 1), (5, 2)].into_iter().collect();
+
+    assert_eq!(storage.get_checked(5), Some(&2));
+    assert_eq!(storage.get_checked(4), None);
+    assert_eq!(storage.get_checked(100), None);
+    assert_eq!(storage.get_checked(Index::MAX), None);
+
+    *storage.get_checked_mut(5).unwrap() += 10;
+    assert_eq!(storage.get_checked(5), Some(&12));
+    assert_eq!(storage.get_checked_mut(1), None);
+    assert_eq!(storage.get_checked_mut(100), None);
+    assert_eq!(storage.get_checked_mut(Index::MAX), None);
+    assert_eq!(storage.len(), 2);
+}
+
+#[test]
+fn clear_keeps_capacity() {
+    let mut storage: IdvStorage<String> = (0..50).map(|i| (i * 2, i.to_string())).collect();
+    let capacity = storage.capacity();
+    let usage = storage.memory_usage();
+    assert!(usage.occupancy_bytes > 0);
+
+    storage.clear();
+    assert_eq!(storage.len(), 0);
+    assert!(storage.is_empty());
+    assert!(!storage.contains(0));
+    assert_eq!(storage.capacity(), capacity);
+    assert_eq!(storage.memory_usage().data_bytes, usage.data_bytes);
+    assert_eq!(
+        storage.memory_usage().occupancy_bytes,
+        usage.occupancy_bytes
+    );
+    storage.audit().unwrap();
+
+    for i in 0..50 {
+        storage.replace(i * 3, i.to_string());
+    }
+
+    assert_eq!(storage.capacity(), capacity);
+    assert_eq!(
+        storage.memory_usage().occupancy_bytes,
+        usage.occupancy_bytes
+    );
+    assert_eq!(storage.get_checked(147).map(String::as_str), Some("49"));
+    assert_eq!(storage.len(), 50);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn index_operators() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30), (8, 80)].into_iter().collect();
+    assert_eq!(storage[3], 30);
+    storage[8] += 1;
+    assert_eq!(storage[8], 81);
+}
+
+#[test]
+#[should_panic(expected = "no component at index 4")]
+fn index_absent_panics() {
+    let storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    let _ = storage[4];
+}
+
+#[test]
+#[should_panic(expected = "no component at index 1000")]
+fn index_mut_absent_panics() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    storage[1_000] = 1;
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "index 1000 is out of range of the storage")]
+fn unchecked_get_out_of_range_asserts() {
+    let storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    unsafe { storage.get_unchecked_ref(1_000) };
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "no component at index 2")]
+fn unchecked_get_vacant_asserts() {
+    let storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    unsafe { storage.get_unchecked_ref(2) };
+}
+
+#[test]
+fn replace_returns_previous_value() {
+    let mut storage = IdvStorage::<String>::default();
+    assert_eq!(storage.replace(7, "a".into()), None);
+    let slot = storage.internal_slot(7);
+
+    assert_eq!(storage.replace(7, "b".into()), Some("a".into()));
+    assert_eq!(storage.replace(7, "c".into()), Some("b".into()));
+    assert_eq!(storage.internal_slot(7), slot);
+    assert_eq!(storage.len(), 1);
+
+    storage.take(7);
+    assert_eq!(storage.replace(7, "d".into()), None);
+}
+
+#[test]
+fn insert_many_matches_single_inserts() {
+    // Repeated indices overwrite, as with single inserts.
+    let items: Vec<(Index, u32)> = (0..500).map(|i| ((i * 37) % 211 + i / 100, i)).collect();
+
+    let mut single = IdvStorage::<u32>::default();
+    single.replace(5, 99);
+
+    for &(idx, v) in &items {
+        single.replace(idx, v);
+    }
+
+    let mut many = IdvStorage::<u32>::default();
+    many.replace(5, 99);
+    many.insert_many(items.iter().copied());
+
+    assert_eq!(many, single);
+    assert_eq!(many.len(), single.len());
+    many.audit().unwrap();
+}
+
+/// Interleaves inserts and removals so that the survivors end up scattered
+/// over the internal slots.
+fn fragmented() -> IdvStorage<u32> {
+    let mut storage = IdvStorage::<u32>::default();
+
+    for round in 0..5 {
+        for i in 0..200 {
+            storage.replace(round * 1_000 + i, i);
+        }
+
+        for i in (0..200).filter(|i| i % 4 != round) {
+            storage.take(round * 1_000 + i);
+        }
+    }
+
+    storage
+}
+
+#[test]
+fn compact_packs_the_lowest_slots() {
+    let mut storage = fragmented();
+    let before: Vec<_> = storage.sorted_iter().map(|(i, &v)| (i, v)).collect();
+    let len = storage.len();
+    assert!(storage.internal_iter_mut().any(|(slot, _)| slot >= len));
+
+    storage.compact();
+
+    let after: Vec<_> = storage.sorted_iter().map(|(i, &v)| (i, v)).collect();
+    assert_eq!(after, before);
+    let mut slots: Vec<_> = storage
+        .keys()
+        .map(|i| storage.internal_slot(i).unwrap())
+        .collect();
+    slots.sort_unstable();
+    assert_eq!(slots, (0..storage.len()).collect::<Vec<_>>());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn fragmentation_rises_and_falls() {
+    let mut storage: IdvStorage<u32> = (0..1_000).map(|i| (i, i)).collect();
+    assert_eq!(storage.fragmentation(), 0.0);
+
+    // Scattered removals below the highest occupied slot leave gaps.
+    for i in (0..999).filter(|i| i % 5 != 0) {
+        storage.take(i);
+    }
+
+    let scattered = storage.fragmentation();
+    assert!(scattered > 0.7, "{}", scattered);
+
+    storage.compact();
+    assert_eq!(storage.fragmentation(), 0.0);
+    assert_eq!(IdvStorage::<u32>::default().fragmentation(), 0.0);
+}
+
+#[test]
+#[should_panic(expected = "no component to remove at index 5, removed twice")]
+fn double_remove_panics() {
+    let mut storage: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+
+    unsafe {
+        assert_eq!(storage.remove(5), 5);
+        storage.remove(5);
+    }
+}
+
+#[test]
+#[should_panic(expected = "no component at index 6, mask and storage out of sync?")]
+fn unprotected_get_absent_panics() {
+    let storage: IdvStorage<u32> = vec![(3, 30), (9, 90)].into_iter().collect();
+    unsafe { storage.get(6) };
+}
+
+#[test]
+#[should_panic(expected = "no component at index 2, mask and storage out of sync?")]
+fn unprotected_get_mut_absent_panics() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    unsafe { storage.get_mut(2) };
+}
+
+#[test]
+#[should_panic(
+    expected = "no component to remove at index 4, removed twice or mask and storage out of sync?"
+)]
+fn unprotected_remove_absent_panics() {
+    let mut storage: IdvStorage<u32> = vec![(3, 30)].into_iter().collect();
+    unsafe { storage.remove(4) };
+}
+
+#[test]
+fn double_take_frees_slot_once() {
+    let mut storage: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+    assert_eq!(storage.take(5), Some(5));
+    assert_eq!(storage.take(5), None);
+    assert_eq!(storage.free_slots_iter().count(), 1);
+    storage.audit().unwrap();
+
+    // Only one index can get the freed slot.
+    storage.replace(20, 20);
+    storage.replace(21, 21);
+    assert_ne!(storage.internal_slot(20), storage.internal_slot(21));
+}
+
+#[test]
+fn disjoint_mut_access() {
+    let mut storage: IdvStorage<u32> = (0..10).map(|i| (i * 2, i)).collect();
+
+    let [a, b, c] = storage.get_disjoint_mut([18, 0, 6]).unwrap();
+    std::mem::swap(a, b);
+    *c += 100;
+    assert_eq!(storage.get_checked(0), Some(&9));
+    assert_eq!(storage.get_checked(18), Some(&0));
+    assert_eq!(storage.get_checked(6), Some(&103));
+
+    // A repeated index would hand out the same component twice.
+    assert!(storage.get_disjoint_mut([2, 4, 2]).is_none());
+
+    // Vacant and out of range indices.
+    assert!(storage.get_disjoint_mut([2, 3]).is_none());
+    assert!(storage.get_disjoint_mut([2, 1_000]).is_none());
+    assert!(storage.get_disjoint_mut::<0>([]).is_some());
+}
+
+#[test]
+fn take_present_absent_and_out_of_range() {
+    let mut storage: IdvStorage<String> = (0..8).map(|i| (i, i.to_string())).collect();
+    let slot = storage.internal_slot(3);
+
+    assert_eq!(storage.take(3), Some("3".into()));
+    assert_eq!(storage.take(3), None);
+    assert_eq!(storage.take(1_000), None);
+    assert_eq!(storage.take(Index::MAX), None);
+    assert_eq!(storage.len(), 7);
+
+    // The slot of the taken component is the next one handed out.
+    storage.replace(20, "20".into());
+    assert_eq!(storage.internal_slot(20), slot);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn extend_from_pairs() {
+    let mut storage: IdvStorage<String> = (0..4).map(|i| (i, i.to_string())).collect();
+    let pairs: Vec<_> = [2, 9, 33, 1_000]
+        .iter()
+        .map(|&i| (i, format!("new {}", i)))
+        .collect();
+    storage.extend(pairs.clone());
+
+    for (idx, v) in &pairs {
+        assert_eq!(storage.get_checked(*idx), Some(v));
+    }
+
+    assert_eq!(storage.get_checked(3).map(String::as_str), Some("3"));
+    assert_eq!(storage.len(), 7);
+
+    // A drained storage rebuilds another one.
+    let mut rebuilt = IdvStorage::<String>::default();
+    rebuilt.extend(storage.clone().drain());
+    assert_eq!(rebuilt, storage);
+    rebuilt.audit().unwrap();
+}
+
+#[test]
+fn collect_pairs_last_write_wins() {
+    let storage: IdvStorage<u32> = (0..50).map(|i| (i * 3, i)).chain([(6, 99)]).collect();
+    assert_eq!(storage.len(), 50);
+
+    for i in 0..150 {
+        let expected = match i {
+            6 => Some(99),
+            _ if i % 3 == 0 => Some(i / 3),
+            _ => None,
+        };
+        assert_eq!(storage.get_checked(i).copied(), expected, "{}", i);
+    }
+
+    storage.audit().unwrap();
+}
+
+#[test]
+fn truncate_at_midpoint() {
+    let mut storage: IdvStorage<u32> = (0..100).map(|i| (i, i)).collect();
+    let (groups, _) = storage.group_stats();
+    storage.truncate(50);
+
+    for i in 0..100 {
+        assert_eq!(storage.contains(i), i < 50, "{}", i);
+    }
+
+    assert_eq!(storage.len(), 50);
+    assert!(storage.group_stats().0 < groups);
+
+    // The freed slots take the next components.
+    let capacity = storage.capacity();
+
+    for i in 50..100 {
+        storage.replace(i + 1_000, i);
+    }
+
+    assert_eq!(storage.capacity(), capacity);
+    storage.truncate(0);
+    assert!(storage.is_empty());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn remove_mask_removes_only_set_bits() {
+    let mut storage: IdvStorage<u32> = (0..64).map(|i| (i, i)).collect();
+    let removed = [0, 7, 8, 31, 63];
+
+    // Vacant and out of range bits are not counted.
+    assert_eq!(
+        storage.remove_mask(&mask(removed.iter().copied().chain([64, 900]))),
+        5
+    );
+
+    for i in 0..64 {
+        assert_eq!(storage.contains(i), !removed.contains(&i), "{}", i);
+    }
+
+    assert_eq!(storage.remove_mask(&mask(removed)), 0);
+    assert_eq!(storage.len(), 59);
+    storage.audit().unwrap();
+}
+
+#[test]
+fn random_operations_keep_invariants() {
+    let mut storage = IdvStorage::<u32>::default();
+    let mut model = std::collections::BTreeMap::new();
+    let mut x = 0x9e37_79b9_u32;
+
+    for round in 0..5_000 {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        let idx = x % 500;
+
+        match x % 10 {
+            0..=5 => assert_eq!(storage.replace(idx, x), model.insert(idx, x)),
+            6..=8 => assert_eq!(storage.take(idx), model.remove(&idx)),
+            _ => {
+                // Clean roughly two thirds of the components.
+                let has = mask(model.keys().copied().filter(|i| i % 3 != x % 3));
+                unsafe { storage.clean(&has) };
+                model.retain(|i, _| !has.contains(*i));
+            }
+        }
+
+        storage
+            .audit()
+            .unwrap_or_else(|err| panic!("round {}: {}", round, err));
+    }
+
+    assert_eq!(storage.len(), model.len());
+    assert!(storage.sorted_iter().eq(model.iter().map(|(&i, v)| (i, v))));
+}
+
+#[test]
+fn removing_the_tail_trims_groups() {
+    let mut storage: IdvStorage<u32> = (0..10).chain(200..210).map(|i| (i, i)).collect();
+    let (groups, _) = storage.group_stats();
+
+    for i in (200..210).rev() {
+        storage.take(i);
+    }
+
+    // The groups past index 9 are released and the trailing data slots are
+    // not kept on the free list, the components before them stay put.
+    assert!(storage.group_stats().0 < groups);
+    assert_eq!(storage.group_stats(), (3, 3));
+    assert_eq!(storage.memory_usage().free_slots, 0);
+
+    for i in 0..10 {
+        assert_eq!(storage.get_checked(i), Some(&i));
+    }
+
+    storage.audit().unwrap();
+}
+
+#[test]
+fn ensure_index_creates_the_group_up_front() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(0, 0);
+    storage.ensure_index(1_000);
+    let (groups, used) = storage.group_stats();
+    assert_eq!((groups, used), (1_000 / 4 + 1, 1));
+    assert!(!storage.contains(1_000));
+    assert_eq!(storage.len(), 1);
+
+    let usage = storage.memory_usage();
+    storage.replace(1_000, 1);
+    storage.replace(500, 2);
+    assert_eq!(storage.group_stats().0, groups);
+    assert_eq!(storage.memory_usage().redirect_bytes, usage.redirect_bytes);
+
+    // Ensuring a covered index changes nothing.
+    storage.ensure_index(3);
+    assert_eq!(storage.group_stats(), (groups, 3));
+    storage.audit().unwrap();
+}
+
+#[test]
+fn raw_pointers_match_checked_access() {
+    let mut storage: IdvStorage<u64> = (0..20).map(|i| (i * 5, u64::from(i))).collect();
+
+    unsafe {
+        let ptr = storage.as_ptr(35).unwrap();
+        assert_eq!(ptr, storage.get_checked(35).unwrap() as *const u64);
+        assert_eq!(*ptr, 7);
+        assert!(storage.as_ptr(36).is_none());
+        assert!(storage.as_ptr(10_000).is_none());
+
+        let ptr = storage.as_mut_ptr(35).unwrap();
+        *ptr = 70;
+        assert!(storage.as_mut_ptr(36).is_none());
+    }
+
+    assert_eq!(storage.get_checked(35), Some(&70));
+}
+
+#[test]
+fn equal_contents_compare_equal() {
+    let ascending: IdvStorage<u32> = (0..20).map(|i| (i, i)).collect();
+
+    // The same contents, reached through removals so that every component
+    // lives in another internal slot.
+    let mut churned = IdvStorage::<u32>::default();
+
+    for i in (0..40).rev() {
+        churned.replace(i, i + 1);
+    }
+
+    for i in 20..40 {
+        churned.take(i);
+    }
+
+    for i in 0..20 {
+        churned.replace(i, i);
+    }
+
+    assert_ne!(churned.internal_slot(0), ascending.internal_slot(0));
+    assert_eq!(churned, ascending);
+    assert_eq!(ascending, churned);
+
+    churned.replace(5, 6);
+    assert_ne!(churned, ascending);
+    churned.replace(5, 5);
+    churned.replace(20, 20);
+    assert_ne!(churned, ascending);
+    churned.take(20);
+    assert_eq!(churned, ascending);
+}
+
+#[test]
+fn restore_returns_to_the_snapshot() {
+    let mut storage: IdvStorage<String> = (0..50).map(|i| (i * 2, i.to_string())).collect();
+    let snapshot = storage.snapshot();
+    let expected = storage.clone();
+
+    for round in 0..3 {
+        for i in 0..30 {
+            storage.take(i * 2);
+        }
+
+        for i in 0..200 {
+            storage.get_or_default(i * 3).push_str("changed");
+        }
+
+        storage.restore(&snapshot);
+        assert_eq!(storage, expected, "round {}", round);
+        storage.audit().unwrap();
+    }
+
+    assert_eq!(snapshot.len(), 50);
+    assert_eq!(snapshot.get_checked(98).map(String::as_str), Some("49"));
+}
+
+#[test]
+fn internal_slots_are_distinct() {
+    let mut storage = IdvStorage::<u32>::default();
+    storage.replace(4, 4);
+    storage.replace(900, 900);
+
+    let (a, b) = (storage.internal_slot(4), storage.internal_slot(900));
+    assert!(a.is_some() && b.is_some());
+    assert_ne!(a, b);
+
+    storage.take(4);
+    assert_eq!(storage.internal_slot(4), None);
+    assert_eq!(storage.internal_slot(5), None);
+    assert_eq!(storage.internal_slot(100_000), None);
+    assert_eq!(storage.internal_slot(900), b);
+}
+
+#[test]
+fn range_counts() {
+    let mut storage: IdvStorage<u32> = (10..30).chain(50..53).map(|i| (i, i)).collect();
+    storage.take(20);
+
+    assert_eq!(storage.count_in_range(0..100), 22);
+    assert_eq!(storage.count_in_range(0..10), 0);
+    assert_eq!(storage.count_in_range(10..20), 10);
+    assert_eq!(storage.count_in_range(15..25), 9);
+    assert_eq!(storage.count_in_range(51..1_000), 2);
+    assert_eq!(storage.count_in_range(25..25), 0);
+
+    assert!(storage.contains_range(10..20));
+    assert!(storage.contains_range(50..53));
+    assert!(!storage.contains_range(10..21));
+    assert!(!storage.contains_range(50..54));
+    assert!(storage.contains_range(5..5));
+}
+
+#[test]
+fn iter_range_yields_in_range_pairs_in_order() {
+    let indices = [900, 3, 64, 17, 18, 250, 0, 63];
+    let storage: IdvStorage<u32> = indices.iter().map(|&i| (i, i + 1)).collect();
+
+    let pairs: Vec<_> = storage.iter_range(3..251).map(|(i, &v)| (i, v)).collect();
+    assert_eq!(
+        pairs,
+        [(3, 4), (17, 18), (18, 19), (63, 64), (64, 65), (250, 251)]
+    );
+
+    assert_eq!(storage.iter_range(0..1).count(), 1);
+    assert_eq!(storage.iter_range(4..17).count(), 0);
+    assert_eq!(storage.iter_range(901..5_000).count(), 0);
+    assert_eq!(storage.iter_range(0..Index::MAX).count(), indices.len());
+}
+
+#[test]
+fn two_mut_access() {
+    let mut storage: IdvStorage<u32> = (0..10).map(|i| (i * 2, i)).collect();
+
+    let (x, y) = storage.get_two_mut(2, 4).unwrap();
+    *x += *y;
+    *y = 0;
+    assert_eq!(storage.get_checked(2), Some(&3));
+    assert_eq!(storage.get_checked(4), Some(&0));
+
+    assert!(storage.get_two_mut(4, 4).is_none());
+    assert!(storage.get_two_mut(4, 5).is_none());
+    assert!(storage.get_two_mut(1_000, 4).is_none());
+}
+
+fn churn(policy: SlotPolicy) -> IdvStorage<u32> {
+    let mut storage: IdvStorage<u32> = IdvStorage::builder().slot_policy(policy).build();
+    storage.extend((0..1_000).map(|i| (i, i)));
+
+    // Free every other slot, refill half of them, then remove the older
+    // components in the upper half.
+    for i in (1..1_000).step_by(2) {
+        storage.take(i);
+    }
+
+    storage.extend((0..250).map(|i| (5_000 + i, i)));
+
+    for i in (500..1_000).step_by(2) {
+        storage.take(i);
+    }
+
+    storage
+}
+
+#[test]
+fn lowest_first_keeps_data_denser() {
+    let lifo = churn(SlotPolicy::Lifo);
+    let lowest = churn(SlotPolicy::LowestFirst);
+    assert_eq!(lifo, lowest);
+    assert_eq!(lowest.len(), 500);
+
+    // Lifo put the new components into the most recently freed, highest
+    // slots, lowest first filled the gaps near the front.
+    assert!(lifo.fragmentation() > 0.4, "{}", lifo.fragmentation());
+    assert_eq!(lowest.fragmentation(), 0.0);
+    assert!(lowest.capacity() <= lifo.capacity());
+    lifo.audit().unwrap();
+    lowest.audit().unwrap();
+}
+
+#[test]
+fn bulk_removals_keep_the_lowest_first_order() {
+    fn lowest_first() -> IdvStorage<u32> {
+        let mut storage = IdvStorage::builder()
+            .slot_policy(SlotPolicy::LowestFirst)
+            .build();
+        // Reversed, so the removals free slots out of order and the lowest
+        // indices hold the trailing slots.
+        storage.extend((0..300).rev().map(|i| (i, i)));
+        storage
+    }
+
+    let removed = |i: Index| !(10..250).contains(&i) || !i.is_multiple_of(3);
+    let mut expected = lowest_first();
+
+    for i in (0..300).filter(|&i| removed(i)) {
+        expected.take(i);
+    }
+
+    let mut retained = lowest_first();
+    retained.retain(|i, _| !removed(i));
+    let mut masked = lowest_first();
+    masked.remove_mask(&mask((0..300).filter(|&i| removed(i))));
+    let mut filtered = lowest_first();
+    filtered.drain_filter(|i, _| removed(i)).for_each(drop);
+    let mut swept = lowest_first();
+
+    for i in (0..300).filter(|&i| removed(i)) {
+        swept.mark_remove(i);
+    }
+
+    swept.sweep();
+
+    for mut storage in [retained, masked, filtered, swept] {
+        assert_eq!(storage, expected);
+        assert!(storage.free_slots_iter().eq(expected.free_slots_iter()));
+        storage.replace(1_000, 1);
+        assert_eq!(storage.internal_slot(1_000), Some(0));
+        storage.audit().unwrap();
+    }
+}
+
+#[test]
+fn clear_with_hands_over_every_component() {
+    let mut storage: IdvStorage<String> = (0..40).map(|i| (i * 3, i.to_string())).collect();
+    storage.take(9);
+    let capacity = storage.capacity();
+
+    let mut received = Vec::new();
+    storage.clear_with(|idx, v| received.push((idx, v)));
+
+    let expected = (0..40).filter(|&i| i != 3).map(|i| (i * 3, i.to_string()));
+    assert!(received.into_iter().eq(expected));
+
+    assert!(storage.is_empty());
+    assert_eq!(storage.capacity(), capacity);
+    storage.audit().unwrap();
+
+    storage.clear_with(|idx, _| panic!("index {} is left", idx));
+}
+
+#[test]
+fn free_and_occupied_slots_cover_the_data() {
+    let mut storage = fragmented();
+    storage.extend((500..520).map(|i| (i, i)));
+
+    let mut slots: Vec<_> = storage
+        .keys()
+        .map(|idx| storage.internal_slot(idx).unwrap())
+        .collect();
+    let occupied = slots.len();
+    slots.extend(storage.free_slots_iter());
+    assert_eq!(slots.len() - occupied, storage.memory_usage().free_slots);
+
+    // Together they are every data slot exactly once.
+    slots.sort_unstable();
+    assert!(slots.iter().copied().eq(0..slots.len()));
+    assert!(slots.len() <= storage.capacity());
+}
+
+#[test]
+fn group_stats_for_a_known_pattern() {
+    let mut storage = IdvStorage::<u32>::default();
+    assert_eq!(storage.group_stats(), (0, 0));
+
+    // Groups of four indices: 0 to 3 share one, 40 starts the eleventh.
+    for i in [0, 1, 3, 9, 40] {
+        storage.replace(i, i);
+    }
+
+    assert_eq!(storage.group_stats(), (11, 3));
+
+    storage.take(9);
+    assert_eq!(storage.group_stats(), (11, 2));
+    storage.take(0);
+    storage.take(1);
+    assert_eq!(storage.group_stats(), (11, 2));
+    storage.take(3);
+    assert_eq!(storage.group_stats(), (11, 1));
+
+    let wide: IdvStorage<u32, 16> = vec![(0, 0), (40, 40)].into_iter().collect();
+    assert_eq!(wide.group_stats(), (3, 2));
+}
+
+#[test]
+fn copy_from_merges_with_overwrites() {
+    let odd: IdvStorage<String> = (0..20)
+        .filter(|i| i % 2 == 1)
+        .map(|i| (i, i.to_string()))
+        .collect();
+    let mut merged: IdvStorage<String> = (0..20)
+        .filter(|i| i % 2 == 0)
+        .map(|i| (i, i.to_string()))
+        .collect();
+
+    // Disjoint storages merge into their union.
+    merged.copy_from(&odd);
+    assert_eq!(merged, (0..20).map(|i| (i, i.to_string())).collect());
+    assert_eq!(odd.len(), 10);
+
+    // Overlapping indices take the value of the copied storage.
+    let overlap: IdvStorage<String> = (15..25).map(|i| (i, format!("new {}", i))).collect();
+    merged.copy_from(&overlap);
+    assert_eq!(merged.len(), 25);
+    assert_eq!(merged.get_checked(14).map(String::as_str), Some("14"));
+    assert_eq!(merged.get_checked(15).map(String::as_str), Some("new 15"));
+    assert_eq!(merged.get_checked(24).map(String::as_str), Some("new 24"));
+    merged.audit().unwrap();
+}
+
+#[test]
+fn map_values_keeps_the_indices() {
+    let mut storage: IdvStorage<u32> = (0..30).map(|i| (i * 5, i)).collect();
+    storage.take(10);
+    let indices: Vec<_> = storage.keys().collect();
+
+    let strings: IdvStorage<String> = storage.map_values(|idx, v| format!("{}@{}", v, idx));
+    assert!(strings.keys().eq(indices));
+    assert_eq!(strings.get_checked(15).map(String::as_str), Some("3@15"));
+    assert_eq!(strings.get_checked(10), None);
+
+    for (idx, v) in &strings {
+        assert_eq!(*v, format!("{}@{}", idx / 5, idx));
+    }
+
+    strings.audit().unwrap();
+}
+
+#[test]
+fn marked_components_wait_for_sweep() {
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let drops = Rc::new(Cell::new(0));
+    let mut storage: IdvStorage<Counted> = (0..10).map(|i| (i, Counted(drops.clone()))).collect();
+    let marked = [2, 5, 6];
+    let slots: Vec<_> = marked
+        .iter()
+        .map(|&i| storage.internal_slot(i).unwrap())
+        .collect();
+
+    for &i in &marked {
+        storage.mark_remove(i);
+    }
+
+    storage.mark_remove(5);
+    storage.mark_remove(50);
+
+    for &i in &marked {
+        assert!(!storage.contains(i));
+        assert!(storage.get_checked(i).is_none());
+    }
+
+    assert_eq!(storage.len(), 7);
+    assert_eq!(storage.keys().count(), 7);
+    assert_eq!(drops.get(), 0);
+
+    // Until the sweep the slots stay taken.
+    storage.replace(20, Counted(drops.clone()));
+    assert!(!slots.contains(&storage.internal_slot(20).unwrap()));
+    storage.audit().unwrap();
+
+    storage.sweep();
+    assert_eq!(drops.get(), 3);
+
+    for i in 30..33 {
+        storage.replace(i, Counted(drops.clone()));
+        assert!(slots.contains(&storage.internal_slot(i).unwrap()));
+    }
+
+    storage.audit().unwrap();
+}
+
+#[test]
+fn batch_get_matches_single_gets() {
+    let storage: IdvStorage<u32> = (0..50).map(|i| (i * 3, i)).collect();
+    let idxs: Vec<Index> = vec![0, 1, 3, 147, 148, 10_000, 3, 99];
+
+    let mut out = Vec::with_capacity(16);
+    out.push(None);
+    storage.batch_get(&idxs, &mut out);
+
+    // Results are appended after what the buffer already held.
+    assert_eq!(out.len(), idxs.len() + 1);
+    let single: Vec<_> = idxs.iter().map(|&i| storage.get_checked(i)).collect();
+    assert_eq!(out[1..], single[..]);
+    assert_eq!(out[4], Some(&49));
+    assert_eq!(out.capacity(), 16);
+}
+
+#[test]
+fn occupancy_ratio_follows_len() {
+    let mut storage = IdvStorage::<u32>::with_capacity(100);
+    assert_eq!(storage.occupancy_ratio(), 0.0);
+
+    for i in 0..50 {
+        storage.replace(i, i);
+    }
+
+    let half = storage.occupancy_ratio();
+    assert_eq!(half, 50.0 / storage.capacity() as f32);
+
+    for i in 50..100 {
+        storage.replace(i, i);
+    }
+
+    let full = storage.occupancy_ratio();
+    assert!(full > half && full <= 1.0, "{} {}", half, full);
+
+    for i in (0..100).step_by(2) {
+        storage.take(i);
+    }
+
+    assert!(storage.occupancy_ratio() < full);
+    storage.clear();
+    assert_eq!(storage.occupancy_ratio(), 0.0);
+}
+
+fn hash_of(storage: &IdvStorage<u32>) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    storage.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn equal_contents_hash_equal() {
+    let compact: IdvStorage<u32> = (0..30).map(|i| (i, i)).collect();
+    let mut churned = fragmented();
+    churned.clear();
+
+    for i in (0..60).rev() {
+        churned.replace(i, i);
+    }
+
+    churned.truncate(30);
+    assert_ne!(churned.internal_slot(0), compact.internal_slot(0));
+    assert_eq!(churned, compact);
+    assert_eq!(hash_of(&churned), hash_of(&compact));
+
+    churned.replace(7, 8);
+    assert_ne!(hash_of(&churned), hash_of(&compact));
+    churned.replace(7, 7);
+    assert_eq!(hash_of(&churned), hash_of(&compact));
+
+    // Moving a value to another index changes the hash too.
+    churned.move_index(29, 30);
+    assert_ne!(hash_of(&churned), hash_of(&compact));
+}
+
+#[test]
+fn free_slots_stay_vacant_under_churn() {
+    for policy in [SlotPolicy::Lifo, SlotPolicy::LowestFirst] {
+        let mut storage = IdvStorage::<u64>::builder().slot_policy(policy).build();
+        let mut x = 0x2468_ace1_u32;
+
+        // Every path that frees a slot, interleaved with inserts that take
+        // one off the free list.
+        for round in 0..3_000 {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            let (a, b) = (x % 300, (x >> 16) % 300);
+
+            match x % 8 {
+                0..=3 => drop(storage.replace(a, u64::from(x))),
+                4 => drop(storage.take(a)),
+                5 => storage.swap(a, b),
+                6 => drop(storage.move_index(a, b)),
+                _ => {
+                    storage.mark_remove(a);
+
+                    if round % 4 == 0 {
+                        storage.sweep();
+                    }
+                }
+            }
+
+            storage
+                .audit()
+                .unwrap_or_else(|err| panic!("{:?} round {}: {}", policy, round, err));
+        }
+    }
+}
+
+#[test]
+fn clean_reporting_lists_the_cleared_indices() {
+    let mut storage: IdvStorage<u32> = (0..50).filter(|i| i % 4 != 1).map(|i| (i, i)).collect();
+    let before: Vec<_> = storage.keys().collect();
+
+    // Vacant and out of range bits are not reported.
+    let has = mask((0..60).filter(|i| i % 3 == 0));
+    let reported = storage.clean_reporting(&has);
+
+    let expected: Vec<_> = before
+        .iter()
+        .copied()
+        .filter(|&i| has.contains(i))
+        .collect();
+    assert_eq!(reported, expected);
+
+    for i in before {
+        assert_eq!(storage.contains(i), !reported.contains(&i), "{}", i);
+    }
+
+    assert!(storage.clean_reporting(&has).is_empty());
+    storage.audit().unwrap();
+}
+
+#[test]
+fn to_dense_matches_get_checked() {
+    let mut storage: IdvStorage<u32> = (0..40).map(|i| (i * 3, i)).collect();
+    storage.take(0);
+    storage.take(117);
+
+    let dense = storage.to_dense();
+    assert_eq!(dense.len(), 115);
+
+    for i in 0..200 {
+        assert_eq!(
+            dense.get(i as usize).and_then(Option::as_ref),
+            storage.get_checked(i),
+            "{}",
+            i
+        );
+    }
+
+    assert!(IdvStorage::<u32>::default().to_dense().is_empty());
+}
+
+#[test]
+fn from_dense_round_trip() {
+    let dense: Vec<_> = (0..101u32).map(|i| (i % 7 < 2).then_some(i)).collect();
+    let storage = IdvStorage::<u32>::from_dense(dense.clone());
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 30);
+    assert_eq!(storage.get_checked(99), Some(&99));
+    assert_eq!(storage.get_checked(100), None);
+
+    // Trailing `None`s are not kept by `to_dense`.
+    assert_eq!(storage.to_dense()[..], dense[..100]);
+
+    let mut sparse: IdvStorage<u32> = (0..30).map(|i| (i * 11, i)).collect();
+    sparse.take(33);
+    let back = IdvStorage::<u32>::from_dense(sparse.to_dense());
+    back.audit().unwrap();
+    assert_eq!(back, sparse);
+}
+
+#[test]
+fn get_array_with_duplicates_and_gaps() {
+    let storage: IdvStorage<u32> = (0..10).map(|i| (i * 2, i)).collect();
+
+    assert_eq!(
+        storage.get_array([4, 3, 4, 1_000, 18, 4]),
+        [Some(&2), None, Some(&2), None, Some(&9), Some(&2)]
+    );
+    assert_eq!(storage.get_array([]), [None::<&u32>; 0]);
+
+    let [a, b] = storage.get_array([6, 6]);
+    assert!(std::ptr::eq(a.unwrap(), b.unwrap()));
+}
+
+#[test]
+fn with_sparse_ratio_keeps_the_contents() {
+    let mut storage: IdvStorage<u32> = (0..300).map(|i| (i * 5 % 997, i)).collect();
+
+    for i in (0..300).step_by(4) {
+        storage.take(i * 5 % 997);
+    }
+
+    let pairs = storage.clone().into_vec();
+
+    let one = storage.with_sparse_ratio::<1>();
+    one.audit().unwrap();
+    assert_eq!(one.clone().into_vec(), pairs);
+
+    let wide = one.with_sparse_ratio::<64>();
+    wide.audit().unwrap();
+    assert_eq!(wide.clone().into_vec(), pairs);
+
+    let odd = wide.with_sparse_ratio::<7>();
+    odd.audit().unwrap();
+    assert_eq!(odd.get_checked(990), Some(&198));
+    assert_eq!(odd.get_checked(0), None);
+
+    let back = odd.with_sparse_ratio::<4>();
+    back.audit().unwrap();
+    assert_eq!(back.into_vec(), pairs);
+}
+
+#[test]
+fn clean_over_mask_patterns() {
+    let patterns: Vec<(&str, BitSet)> = vec![
+        ("empty", BitSet::new()),
+        ("everything", mask(0..1_000)),
+        ("even", mask((0..1_000).step_by(2))),
+        ("one group", mask(64..68)),
+        ("vacant only", mask((1..600).step_by(3))),
+        ("past the end", mask(600..5_000)),
+        ("far bits", mask(vec![0, 4_095, 100_000, 1 << 20])),
+    ];
+
+    for (name, has) in patterns {
+        let mut storage: IdvStorage<u32> = (0..200).map(|i| (i * 3, i)).collect();
+        storage.take(300);
+        let mut expected = storage.clone();
+        expected.retain(|idx, _| !has.contains(idx));
+
+        unsafe { storage.clean(&has) };
+        storage.audit().unwrap();
+        assert_eq!(storage, expected, "{}", name);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Body {
+    pos: [f32; 2],
+    vel: [f32; 2],
+}
+
+#[test]
+fn project_mut_reaches_one_field() {
+    let body = Body {
+        pos: [0.0, 0.0],
+        vel: [1.0, 2.0],
+    };
+    let mut storage: IdvStorage<Body> = (0..5).map(|i| (i * 2, body.clone())).collect();
+
+    let pos = storage.project_mut(4, |b| &mut b.pos).unwrap();
+    pos[1] += 3.0;
+    *storage.project_mut(4, |b| &mut b.vel[0]).unwrap() = 9.0;
+
+    let moved = storage.get_checked(4).unwrap();
+    assert_eq!(moved.pos, [0.0, 3.0]);
+    assert_eq!(moved.vel, [9.0, 2.0]);
+    assert_eq!(storage.get_checked(6), Some(&body));
+
+    let mut called = false;
+    assert!(storage
+        .project_mut(5, |b| {
+            called = true;
+            &mut b.pos
+        })
+        .is_none());
+    assert!(!called);
+}
+
+/// A 64 byte component, which stays contiguous even with `cache-aligned`.
+type Block = [u64; 8];
+
+#[test]
+fn contiguous_slice_only_when_fully_dense() {
+    let block = |i: u64| [i; 8];
+    let mut storage: IdvStorage<Block> = (0..40).map(|i| (i, block(i.into()))).collect();
+    let expected: Vec<_> = (0..40).map(block).collect();
+    assert_eq!(storage.as_contiguous_slice(), Some(&expected[..]));
+
+    // A hole below the highest index.
+    storage.take(12);
+    assert_eq!(storage.as_contiguous_slice(), None);
+    storage.compact();
+    assert_eq!(storage.as_contiguous_slice(), None);
+
+    // Fully occupied again, but compacting moved index 39 into the hole.
+    storage.replace(12, block(12));
+    assert!(storage.keys().eq(0..40));
+    assert_eq!(storage.as_contiguous_slice(), None);
+
+    storage.sort_by_index();
+    assert_eq!(storage.as_contiguous_slice(), Some(&expected[..]));
+
+    // Smaller components are padded to a cache line with `cache-aligned`.
+    let small: IdvStorage<u32> = (0..10).map(|i| (i, i)).collect();
+    let dense = small.as_contiguous_slice();
+    assert_eq!(dense.is_some(), cfg!(not(feature = "cache-aligned")));
+    assert!(dense.is_none_or(|slice| slice.iter().copied().eq(0..10)));
+}
@@ -0,0 +1,128 @@
+//! Storages staying consistent when a component destructor or `Clone` impl
+//! panics.
+
+use specs::storage::UnprotectedStorage;
+use specs_idvs::IdvStorage;
+use std::panic::{self, AssertUnwindSafe, RefUnwindSafe, UnwindSafe};
+use std::sync::{Arc, Mutex};
+
+const _: fn() = || {
+    fn unwind_safe<T: UnwindSafe + RefUnwindSafe>() {}
+    unwind_safe::<IdvStorage<u32>>();
+};
+
+type Log = Arc<Mutex<Vec<u32>>>;
+
+/// Records its id when dropped, and panics there or when cloned if told to.
+#[derive(Debug)]
+struct Bomb {
+    id: u32,
+    panic_on_drop: bool,
+    panic_on_clone: bool,
+    drops: Log,
+}
+
+impl Clone for Bomb {
+    fn clone(&self) -> Self {
+        assert!(!self.panic_on_clone, "clone of {} panicked", self.id);
+
+        Bomb {
+            id: self.id,
+            panic_on_drop: false,
+            panic_on_clone: false,
+            drops: self.drops.clone(),
+        }
+    }
+}
+
+impl Drop for Bomb {
+    fn drop(&mut self) {
+        self.drops.lock().unwrap().push(self.id);
+        assert!(!self.panic_on_drop, "drop of {} panicked", self.id);
+    }
+}
+
+fn bombs(drops: &Log, n: u32, panic_on_drop: u32, panic_on_clone: u32) -> IdvStorage<Bomb> {
+    (0..n)
+        .map(|id| {
+            let bomb = Bomb {
+                id,
+                panic_on_drop: id == panic_on_drop,
+                panic_on_clone: id == panic_on_clone,
+                drops: drops.clone(),
+            };
+            (id * 3, bomb)
+        })
+        .collect()
+}
+
+fn ids(storage: &IdvStorage<Bomb>) -> Vec<u32> {
+    storage.sorted_iter().map(|(_, b)| b.id).collect()
+}
+
+fn assert_dropped_once(drops: &Log) {
+    let mut drops = drops.lock().unwrap().clone();
+    let len = drops.len();
+    drops.sort_unstable();
+    drops.dedup();
+    assert_eq!(drops.len(), len, "a component was dropped twice");
+}
+
+#[test]
+fn panicking_drop_in_clear() {
+    let drops = Log::default();
+    let mut storage = bombs(&drops, 40, 17, u32::MAX);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| storage.clear()));
+    assert!(result.is_err());
+    storage.audit().unwrap();
+    assert!(storage.is_empty());
+    assert_dropped_once(&drops);
+
+    storage.extend(bombs(&drops, 10, u32::MAX, u32::MAX));
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 10);
+}
+
+#[test]
+fn panicking_drop_on_overwrite() {
+    let drops = Log::default();
+    let mut storage = bombs(&drops, 20, 5, u32::MAX);
+    let replacement = Bomb {
+        id: 100,
+        panic_on_drop: false,
+        panic_on_clone: false,
+        drops: drops.clone(),
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        storage.insert(15, replacement);
+    }));
+    assert!(result.is_err());
+    storage.audit().unwrap();
+    assert_eq!(storage.len(), 20);
+    assert_eq!(storage.get_checked(15).unwrap().id, 100);
+    assert_eq!(*drops.lock().unwrap(), [5]);
+
+    drop(storage);
+    assert_dropped_once(&drops);
+}
+
+#[test]
+fn panicking_clone() {
+    let drops = Log::default();
+    let source = bombs(&drops, 30, u32::MAX, 12);
+    let before = ids(&source);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| source.clone()));
+    assert!(result.is_err());
+    source.audit().unwrap();
+    assert_eq!(ids(&source), before);
+
+    let mut target = bombs(&drops, 50, u32::MAX, u32::MAX);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| target.clone_from(&source)));
+    assert!(result.is_err());
+    target.audit().unwrap();
+    assert!(target.is_empty());
+    assert_eq!(ids(&source), before);
+}
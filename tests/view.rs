@@ -0,0 +1,41 @@
+//! `ReadView` handles shared between threads.
+
+use specs::hibitset::BitSet;
+use specs_idvs::IdvStorage;
+use std::thread;
+
+#[test]
+fn threads_read_the_same_view() {
+    let storage: IdvStorage<u64> = (0..10_000)
+        .filter(|i| i % 3 != 0)
+        .map(|i| (i, u64::from(i)))
+        .collect();
+    let view = storage.as_read();
+
+    let mut mask = BitSet::new();
+
+    for i in 0..10_000 {
+        mask.add(i);
+    }
+
+    let expected: u64 = storage.values().sum();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let mask = &mask;
+                scope.spawn(move || {
+                    assert_eq!(view.len(), 6_666);
+                    assert!(view.contains(4) && !view.contains(3));
+                    let idx = 1_000 + t;
+                    assert_eq!(view.get_checked(idx).is_some(), idx % 3 != 0);
+                    view.iter(mask).map(|(_, v)| v).sum::<u64>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    });
+}
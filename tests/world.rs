@@ -0,0 +1,239 @@
+//! Drives `IdvStorage` through a specs `World`, where a mismatch between the
+//! storage and specs' component mask shows up as wrong join results.
+
+use specs::prelude::*;
+use specs_idvs::IdvStorage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = IdvStorage<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = IdvStorage<Self>;
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.register::<Pos>();
+    world.register::<Vel>();
+    world
+}
+
+/// Spawns `n` entities. Every entity with an even id gets a `Pos` and every
+/// entity with an id divisible by three a `Vel`, so entity 0 has both.
+fn populate(world: &mut World, n: u32) -> Vec<Entity> {
+    (0..n)
+        .map(|i| {
+            let mut builder = world.create_entity();
+
+            if i % 2 == 0 {
+                builder = builder.with(Pos(i));
+            }
+
+            if i % 3 == 0 {
+                builder = builder.with(Vel(i * 10));
+            }
+
+            builder.build()
+        })
+        .collect()
+}
+
+fn positions(world: &World) -> Vec<(u32, Pos)> {
+    let entities = world.entities();
+    let pos = world.read_storage::<Pos>();
+    (&entities, &pos)
+        .join()
+        .map(|(e, p)| (e.id(), *p))
+        .collect()
+}
+
+fn pairs(world: &World) -> Vec<(u32, Pos, Vel)> {
+    let entities = world.entities();
+    let pos = world.read_storage::<Pos>();
+    let vel = world.read_storage::<Vel>();
+    (&entities, &pos, &vel)
+        .join()
+        .map(|(e, p, v)| (e.id(), *p, *v))
+        .collect()
+}
+
+#[test]
+fn single_join() {
+    let mut world = world();
+    populate(&mut world, 100);
+
+    let expected: Vec<_> = (0..100).step_by(2).map(|i| (i, Pos(i))).collect();
+    assert_eq!(positions(&world), expected);
+}
+
+#[test]
+fn pair_join() {
+    let mut world = world();
+    populate(&mut world, 100);
+
+    let expected: Vec<_> = (0..100)
+        .step_by(6)
+        .map(|i| (i, Pos(i), Vel(i * 10)))
+        .collect();
+    assert_eq!(pairs(&world), expected);
+}
+
+#[test]
+fn mutable_join() {
+    let mut world = world();
+    populate(&mut world, 100);
+
+    {
+        let mut pos = world.write_storage::<Pos>();
+        let vel = world.read_storage::<Vel>();
+
+        for (p, v) in (&mut pos, &vel).join() {
+            p.0 += v.0;
+        }
+    }
+
+    let expected: Vec<_> = (0..100)
+        .step_by(2)
+        .map(|i| (i, Pos(if i % 3 == 0 { i + i * 10 } else { i })))
+        .collect();
+    assert_eq!(positions(&world), expected);
+}
+
+#[test]
+fn insert_and_remove_mid_session() {
+    let mut world = world();
+    let entities = populate(&mut world, 60);
+
+    {
+        let mut pos = world.write_storage::<Pos>();
+        let mut vel = world.write_storage::<Vel>();
+
+        for &e in &entities {
+            match e.id() % 4 {
+                0 => {
+                    pos.remove(e);
+                }
+                1 => {
+                    pos.insert(e, Pos(e.id() + 1000)).unwrap();
+                }
+                _ => {}
+            }
+        }
+
+        // Entity 0 loses its velocity too and then gets both back, so index 0
+        // is vacated and reused.
+        vel.remove(entities[0]);
+        pos.insert(entities[0], Pos(7)).unwrap();
+        vel.insert(entities[0], Vel(8)).unwrap();
+    }
+
+    let pos_of = |i: u32| match (i, i % 4) {
+        (0, _) => Some(Pos(7)),
+        (_, 0) => None,
+        (_, 1) => Some(Pos(i + 1000)),
+        (_, 2) => Some(Pos(i)),
+        _ => None,
+    };
+    let vel_of = |i: u32| match (i, i % 3) {
+        (0, _) => Some(Vel(8)),
+        (_, 0) => Some(Vel(i * 10)),
+        _ => None,
+    };
+
+    let expected: Vec<_> = (0..60).filter_map(|i| Some((i, pos_of(i)?))).collect();
+    assert_eq!(positions(&world), expected);
+
+    let expected: Vec<_> = (0..60)
+        .filter_map(|i| Some((i, pos_of(i)?, vel_of(i)?)))
+        .collect();
+    assert_eq!(pairs(&world), expected);
+}
+
+#[test]
+fn deleted_entities_and_recycled_ids() {
+    let mut world = world();
+    let entities = populate(&mut world, 40);
+
+    for &e in entities.iter().filter(|e| e.id() % 5 == 0) {
+        world.delete_entity(e).unwrap();
+    }
+
+    world.maintain();
+
+    let expected: Vec<_> = (0..40)
+        .filter(|i| i % 2 == 0 && i % 5 != 0)
+        .map(|i| (i, Pos(i)))
+        .collect();
+    assert_eq!(positions(&world), expected);
+
+    // The deleted ids are handed out again, and the new entities must not see
+    // the components of the old ones.
+    let fresh: Vec<_> = (0..8)
+        .map(|i| world.create_entity().with(Vel(500 + i)).build())
+        .collect();
+    assert!(fresh.iter().any(|e| e.id() % 5 == 0 && e.id() < 40));
+
+    let pos = world.read_storage::<Pos>();
+    let vel = world.read_storage::<Vel>();
+
+    for &e in &fresh {
+        assert_eq!(pos.get(e), None);
+        assert!(vel.get(e).is_some());
+    }
+
+    assert_eq!(
+        (&pos, &vel).join().count(),
+        (0..40).filter(|i| i % 6 == 0 && i % 5 != 0).count()
+    );
+}
+
+#[test]
+fn clearing_a_storage() {
+    let mut world = world();
+    let entities = populate(&mut world, 30);
+
+    world.write_storage::<Pos>().clear();
+
+    assert!(positions(&world).is_empty());
+    assert!(pairs(&world).is_empty());
+    assert_eq!(world.read_storage::<Vel>().join().count(), 10);
+
+    world
+        .write_storage::<Pos>()
+        .insert(entities[3], Pos(3))
+        .unwrap();
+    assert_eq!(positions(&world), vec![(3, Pos(3))]);
+    assert_eq!(pairs(&world), vec![(3, Pos(3), Vel(30))]);
+}
+
+#[test]
+fn try_default_builds_the_storage() {
+    use specs::storage::TryDefault;
+
+    assert!(IdvStorage::<Pos>::try_default().unwrap().is_empty());
+
+    // Registering builds the storage through `TryDefault` as well.
+    let mut world = World::new();
+    world.register::<Pos>();
+    let entities: Vec<_> = (0..5).map(|_| world.create_entity().build()).collect();
+
+    {
+        let mut pos = world.write_storage::<Pos>();
+
+        for &e in &entities[1..] {
+            assert_eq!(pos.insert(e, Pos(e.id() * 2)).unwrap(), None);
+        }
+    }
+
+    assert_eq!(
+        positions(&world),
+        vec![(1, Pos(2)), (2, Pos(4)), (3, Pos(6)), (4, Pos(8))]
+    );
+}
@@ -0,0 +1,34 @@
+//! Scrubbing removed components, with the `zeroize` feature.
+
+#![cfg(feature = "zeroize")]
+
+use specs_idvs::IdvStorage;
+use std::{mem, slice};
+
+type Key = [u8; 32];
+
+fn slot_bytes(ptr: *const Key) -> Vec<u8> {
+    unsafe { slice::from_raw_parts(ptr.cast::<u8>(), mem::size_of::<Key>()).to_vec() }
+}
+
+#[test]
+fn removed_bytes_are_zeroed() {
+    let mut storage: IdvStorage<Key> = (0..8).map(|i| (i, [0xa5; 32])).collect();
+
+    // Removing a component leaves its data slot allocated, so the pointer
+    // still points into the storage's buffer afterwards. Index 7 is also
+    // the last slot, which gets trimmed.
+    for idx in [3, 7] {
+        let ptr = unsafe { storage.as_ptr(idx).unwrap() };
+        assert_eq!(slot_bytes(ptr), [0xa5; 32]);
+
+        assert!(storage.remove_zeroize(idx));
+        assert_eq!(slot_bytes(ptr), [0; 32]);
+        assert!(!storage.contains(idx));
+    }
+
+    assert!(!storage.remove_zeroize(3));
+    assert_eq!(storage.get_checked(4), Some(&[0xa5; 32]));
+    assert_eq!(storage.len(), 6);
+    storage.audit().unwrap();
+}